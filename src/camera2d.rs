@@ -0,0 +1,242 @@
+use crate::{object::TObject, generate_get_name, generate_get_id};
+
+use raylib::math::{Vector2, Rectangle};
+use crate::object::Object;
+use std::cell::{Cell, RefCell};
+
+// Smallest zoom a camera is drawn with, as 0 would divide by zero converting positions.
+pub const MIN_ZOOM: f32 = 0.01;
+
+// Offset, target, rotation and zoom of a camera, enough to convert between
+// world and screen space.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraView
+{
+    pub offset: Vector2,
+    pub target: Vector2,
+    pub rotation: f32,
+    pub zoom: f32,
+}
+
+impl CameraView
+{
+    pub fn world_to_screen(&self, world: Vector2) -> Vector2
+    {
+        let relative = (world - self.target) * self.zoom;
+        rotate(relative, self.rotation) + self.offset
+    }
+
+    pub fn screen_to_world(&self, screen: Vector2) -> Vector2
+    {
+        let relative = (screen - self.offset) / self.zoom;
+        rotate(relative, -self.rotation) + self.target
+    }
+}
+
+fn rotate(vec: Vector2, degrees: f32) -> Vector2
+{
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Vector2::new(vec.x * cos - vec.y * sin, vec.x * sin + vec.y * cos)
+}
+
+// The camera scripts convert positions with, and shakes requested by scripts.
+thread_local!
+{
+    static ACTIVE_VIEW: Cell<Option<CameraView>> = Cell::new(None);
+    static REQUESTED_SHAKES: RefCell<Vec<(f32, f32)>> = RefCell::new(vec![]);
+}
+
+pub fn set_active_view(view: Option<CameraView>)
+{
+    ACTIVE_VIEW.with(|active| active.set(view));
+}
+
+pub fn active_view() -> Option<CameraView>
+{
+    ACTIVE_VIEW.with(|active| active.get())
+}
+
+pub fn request_shake(intensity: f32, duration: f32)
+{
+    REQUESTED_SHAKES.with(|shakes| shakes.borrow_mut().push((intensity, duration)));
+}
+
+pub fn take_shake_requests() -> Vec<(f32, f32)>
+{
+    REQUESTED_SHAKES.with(|shakes| shakes.borrow_mut().drain(..).collect())
+}
+
+pub struct Camera2D
+{
+    pub object: Object,
+
+    pub offset: Vector2,
+    pub target: Vector2,
+    // Target at the start of the last fixed update, used to interpolate drawing.
+    pub previous_target: Vector2,
+    pub rotation: f32,
+    pub zoom: f32,
+    // The first active camera in a scene is used to draw it.
+    pub active: bool,
+
+    // Name of the object the camera follows.
+    pub follow: String,
+    // How quickly the camera catches up to its target, 0 snaps straight to it.
+    pub smoothing: f32,
+    // Half size of the area around the target the followed object can move in
+    // without moving the camera.
+    pub dead_zone: Vector2,
+    // Area the view is kept inside of, unused when the size is 0.
+    pub bounds: Rectangle,
+
+    pub shake_intensity: f32,
+    pub shake_duration: f32,
+    pub shake_time_left: f32,
+    shake_offset: Vector2,
+}
+
+impl TObject for Camera2D
+{
+    fn new() -> Self
+    {
+        Camera2D
+        {
+            object: Object::new(),
+
+            offset: Vector2::zero(),
+            target: Vector2::zero(),
+            previous_target: Vector2::zero(),
+            rotation: 0.0,
+            zoom: 1.0,
+            active: true,
+
+            follow: String::new(),
+            smoothing: 0.0,
+            dead_zone: Vector2::zero(),
+            bounds: Rectangle::new(0.0, 0.0, 0.0, 0.0),
+
+            shake_intensity: 0.0,
+            shake_duration: 0.0,
+            shake_time_left: 0.0,
+            shake_offset: Vector2::zero(),
+        }
+    }
+
+    generate_get_name!();
+    generate_get_id!();
+
+    fn get_obj(&mut self) -> &mut Object
+    {
+        &mut self.object
+    }
+
+    fn get_obj_ref(&self) -> &Object
+    {
+        &self.object
+    }
+}
+
+impl Camera2D
+{
+    // Moves towards the followed position, if any, then applies bounds and shake.
+    pub fn update(&mut self, delta: f32, follow_pos: Option<Vector2>)
+    {
+        self.previous_target = self.target;
+
+        if let Some(follow_pos) = follow_pos
+        {
+            let mut desired = self.target;
+            let difference = follow_pos - self.target;
+
+            if difference.x.abs() > self.dead_zone.x
+            {
+                desired.x = follow_pos.x - self.dead_zone.x * difference.x.signum();
+            }
+            if difference.y.abs() > self.dead_zone.y
+            {
+                desired.y = follow_pos.y - self.dead_zone.y * difference.y.signum();
+            }
+
+            if self.smoothing > 0.0
+            {
+                self.target = self.target.lerp(desired, 1.0 - (-self.smoothing * delta).exp());
+            }
+            else
+            {
+                self.target = desired;
+            }
+        }
+
+        self.clamp_to_bounds();
+
+        self.shake_time_left = (self.shake_time_left - delta).max(0.0);
+        if self.shake_time_left > 0.0 && self.shake_duration > 0.0
+        {
+            let strength = self.shake_intensity * self.shake_time_left / self.shake_duration;
+            // Cheap repeatable noise so replays shake the same way.
+            let t = self.shake_time_left * 60.0;
+            self.shake_offset = Vector2::new((t * 12.9898).sin(), (t * 78.233).cos()) * strength;
+        }
+        else
+        {
+            self.shake_offset = Vector2::zero();
+        }
+    }
+
+    pub fn shake(&mut self, intensity: f32, duration: f32)
+    {
+        self.shake_intensity = intensity;
+        self.shake_duration = duration;
+        self.shake_time_left = duration;
+    }
+
+    // The offset is treated as half of the visible area, as it is for a centred camera.
+    fn clamp_to_bounds(&mut self)
+    {
+        if self.bounds.width <= 0.0 || self.bounds.height <= 0.0
+        {
+            return;
+        }
+
+        let half_view = self.offset / self.zoom();
+        let clamp_axis = |value: f32, min: f32, size: f32, half: f32|
+        {
+            if size <= half * 2.0 { min + size / 2.0 }
+            else { value.clamp(min + half, min + size - half) }
+        };
+
+        self.target.x = clamp_axis(self.target.x, self.bounds.x, self.bounds.width, half_view.x);
+        self.target.y = clamp_axis(self.target.y, self.bounds.y, self.bounds.height, half_view.y);
+    }
+
+    // Zoom of 0 or less, or not a number, is drawn at the smallest zoom instead.
+    pub fn zoom(&self) -> f32
+    {
+        self.zoom.max(MIN_ZOOM)
+    }
+
+    // Draws the camera where it is, without moving in from where it was last fixed update.
+    pub fn reset_interpolation(&mut self)
+    {
+        self.previous_target = self.target;
+    }
+
+    // The view as of the last fixed update, including any shake.
+    pub fn view(&self) -> CameraView
+    {
+        self.interpolated_view(1.0)
+    }
+
+    // The view between the last two fixed updates by alpha, the same way objects are
+    // drawn, so followed objects stay still on screen.
+    pub fn interpolated_view(&self, alpha: f32) -> CameraView
+    {
+        CameraView
+        {
+            offset: self.offset + self.shake_offset,
+            target: self.previous_target.lerp(self.target, alpha),
+            rotation: self.rotation,
+            zoom: self.zoom(),
+        }
+    }
+}
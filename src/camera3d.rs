@@ -0,0 +1,64 @@
+use crate::{object::TObject, generate_get_name, generate_get_id};
+
+use raylib::math::Vector3;
+use crate::object::Object;
+
+pub struct Camera3D
+{
+    pub object: Object,
+
+    pub position: Vector3,
+    pub target: Vector3,
+    pub up: Vector3,
+    // Field of view in degrees, or the view width when orthographic.
+    pub fovy: f32,
+    pub orthographic: bool,
+    // The first active 3d camera in a scene is used to draw its 3d objects.
+    pub active: bool,
+}
+
+impl TObject for Camera3D
+{
+    fn new() -> Self
+    {
+        Camera3D
+        {
+            object: Object::new(),
+
+            position: Vector3::new(0.0, 10.0, 10.0),
+            target: Vector3::zero(),
+            up: Vector3::up(),
+            fovy: 45.0,
+            orthographic: false,
+            active: true,
+        }
+    }
+
+    generate_get_name!();
+    generate_get_id!();
+
+    fn get_obj(&mut self) -> &mut Object
+    {
+        &mut self.object
+    }
+
+    fn get_obj_ref(&self) -> &Object
+    {
+        &self.object
+    }
+}
+
+impl Camera3D
+{
+    pub fn to_raylib(&self) -> raylib::camera::Camera3D
+    {
+        if self.orthographic
+        {
+            raylib::camera::Camera3D::orthographic(self.position, self.target, self.up, self.fovy)
+        }
+        else
+        {
+            raylib::camera::Camera3D::perspective(self.position, self.target, self.up, self.fovy)
+        }
+    }
+}
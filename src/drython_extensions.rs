@@ -1,11 +1,20 @@
 use drython::types::ExFnRef;
-use crate::transform::Transform2D;
-use crate::object::Object2D;
+use crate::transform::{Transform2D, Transform3D};
+use crate::object::{Object2D, Object3D, Camera2D, Camera3D, active_view, request_shake};
 use std::collections::HashMap;
 use drython::types::{Token, Runner};
-use raylib::prelude::Vector2;
+use raylib::prelude::{Vector2, Vector3, Quaternion, Rectangle, Color};
 
-use crate::object::{Object, TObject};
+use crate::object::{Object, TObject, ObjectRequest, with_object_info, find_objects, request_object};
+use crate::time;
+use crate::random;
+use crate::save_data;
+use crate::logging::{self, Level};
+use crate::input::{self, Binding};
+use crate::scene_manager::{request_scene, request_spawn, request_destroy, SceneRequest};
+use crate::scene_manager::object_registry::{write_vector2, write_vector3};
+use yaml_rust::Yaml;
+use yaml_rust::yaml::Hash;
 
 pub fn vector2_to_token(vec: Vector2) -> Token
 {
@@ -16,127 +25,1053 @@ pub fn token_to_vector2(token: &Token) -> Vector2
 {
     if let Token::Collection(c) = token
     {
-        if c.len() == 2
+        let values: Vec<f32> = c.iter().filter_map(token_to_f32).collect();
+        if values.len() == 2
         {
-            match (&c[0], &c[1])
-            {
-                (Token::Float(x), Token::Float(y)) => { return Vector2::new(*x,*y); }
-                _ => ()
-            }
+            return Vector2::new(values[0], values[1]);
         }
     }
 
     Vector2::zero()
 }
 
+pub fn vector3_to_token(vec: Vector3) -> Token
+{
+    Token::Collection(vec![Token::Float(vec.x), Token::Float(vec.y), Token::Float(vec.z)])
+}
+
+pub fn token_to_vector3(token: &Token) -> Vector3
+{
+    if let Token::Collection(c) = token
+    {
+        let values: Vec<f32> = c.iter().filter_map(token_to_f32).collect();
+        if values.len() == 3
+        {
+            return Vector3::new(values[0], values[1], values[2]);
+        }
+    }
+
+    Vector3::zero()
+}
+
+// Scripts see rotations as euler angles in degrees, which raylib's to_euler already gives.
+pub fn quaternion_to_token(rot: Quaternion) -> Token
+{
+    vector3_to_token(rot.to_euler())
+}
+
+pub fn token_to_quaternion(token: &Token) -> Quaternion
+{
+    let euler = token_to_vector3(token);
+    Quaternion::from_euler(euler.x.to_radians(), euler.y.to_radians(), euler.z.to_radians())
+}
+
+pub fn token_to_i32(token: &Token) -> i32
+{
+    match token
+    {
+        Token::Int(i) => *i,
+        Token::Float(f) => *f as i32,
+        _ => 0
+    }
+}
+
+pub fn bool_to_token(value: bool) -> Token
+{
+    Token::Bool(value)
+}
+
+pub fn token_to_bool(token: &Token) -> bool
+{
+    match token
+    {
+        Token::Bool(b) => *b,
+        Token::Int(i) => *i != 0,
+        _ => false
+    }
+}
+
+pub fn color_to_token(color: Color) -> Token
+{
+    Token::Collection(vec![Token::Int(color.r as i32), Token::Int(color.g as i32), Token::Int(color.b as i32), Token::Int(color.a as i32)])
+}
+
+// Takes [r, g, b] or [r, g, b, a] from 0 to 255.
+pub fn token_to_color(token: &Token) -> Color
+{
+    if let Token::Collection(c) = token
+    {
+        let channels: Vec<u8> = c.iter().filter_map(token_to_f32).map(|x| x.clamp(0.0, 255.0) as u8).collect();
+        match channels.len()
+        {
+            3 => { return Color::new(channels[0], channels[1], channels[2], 255); }
+            4 => { return Color::new(channels[0], channels[1], channels[2], channels[3]); }
+            _ => ()
+        }
+    }
+
+    Color::WHITE
+}
+
+pub fn rectangle_to_token(rect: Rectangle) -> Token
+{
+    Token::Collection(vec![Token::Float(rect.x), Token::Float(rect.y), Token::Float(rect.width), Token::Float(rect.height)])
+}
+
+pub fn token_to_rectangle(token: &Token) -> Rectangle
+{
+    if let Token::Collection(c) = token
+    {
+        let values: Vec<f32> = c.iter().filter_map(token_to_f32).collect();
+        if values.len() == 4
+        {
+            return Rectangle::new(values[0], values[1], values[2], values[3]);
+        }
+    }
+
+    Rectangle::new(0.0, 0.0, 0.0, 0.0)
+}
+
+pub fn token_to_f32(token: &Token) -> Option<f32>
+{
+    match token
+    {
+        Token::Float(f) => Some(*f),
+        Token::Int(i) => Some(*i as f32),
+        _ => None
+    }
+}
+
+pub fn token_to_float(token: &Token) -> f32
+{
+    token_to_f32(token).unwrap_or(0.0)
+}
+
+// True for a collection of count numbers, ints or floats.
+fn is_numbers(token: &Token, count: usize) -> bool
+{
+    match token
+    {
+        Token::Collection(c) => c.len() == count && c.iter().all(|x| token_to_f32(x).is_some()),
+        _ => false,
+    }
+}
+
 pub type ExVarMap = HashMap<String, Token>;
 
-fn make_ex_var_map(name: &str, value: Token) -> (String, Token)
+// A field scripts can read and write, by its type. Every conversion between tokens and
+// fields goes through these, so reading, writing and setting from other objects agree.
+pub enum ExVar<'a>
 {
-    (name.to_string(), value)
+    Int(&'a mut i32),
+    Float(&'a mut f32),
+    Bool(&'a mut bool),
+    String(&'a mut String),
+    Vector2(&'a mut Vector2),
+    Vector3(&'a mut Vector3),
+    // Given to scripts as euler angles in degrees.
+    Quaternion(&'a mut Quaternion),
+    Color(&'a mut Color),
+    Rectangle(&'a mut Rectangle),
 }
 
-pub trait DrythonExRef
+impl<'a> ExVar<'a>
 {
-    fn get_drython_vars(&mut self) -> ExVarMap; 
-    fn set_my_vars(&mut self, runner: &mut Runner, identifiers: &str);
+    pub fn to_token(&self) -> Token
+    {
+        match self
+        {
+            ExVar::Int(value) => Token::Int(**value),
+            ExVar::Float(value) => Token::Float(**value),
+            ExVar::Bool(value) => bool_to_token(**value),
+            ExVar::String(value) => Token::String((*value).clone()),
+            ExVar::Vector2(value) => vector2_to_token(**value),
+            ExVar::Vector3(value) => vector3_to_token(**value),
+            ExVar::Quaternion(value) => quaternion_to_token(**value),
+            ExVar::Color(value) => color_to_token(**value),
+            ExVar::Rectangle(value) => rectangle_to_token(**value),
+        }
+    }
+
+    // Reads the script's copy of the variable back into the field.
+    pub fn update(self, runner: &mut Runner, name: &str)
+    {
+        match self
+        {
+            ExVar::Int(value) => runner.update_variable_conversion((name, value), token_to_i32),
+            ExVar::Float(value) => runner.update_variable_conversion((name, value), token_to_float),
+            ExVar::Bool(value) => runner.update_variable_conversion((name, value), token_to_bool),
+            ExVar::String(value) => runner.update_variable((name, value)),
+            ExVar::Vector2(value) => runner.update_variable_conversion((name, value), token_to_vector2),
+            ExVar::Vector3(value) => runner.update_variable_conversion((name, value), token_to_vector3),
+            ExVar::Quaternion(value) => runner.update_variable_conversion((name, value), token_to_quaternion),
+            ExVar::Color(value) => runner.update_variable_conversion((name, value), token_to_color),
+            ExVar::Rectangle(value) => runner.update_variable_conversion((name, value), token_to_rectangle),
+        }
+    }
+
+    // Sets the field from a token, with an error naming what was expected when it does
+    // not fit. Ints are accepted wherever floats are.
+    pub fn set(self, value: &Token) -> Result<(), String>
+    {
+        let expected = match (self, value)
+        {
+            (ExVar::Int(field), Token::Int(_) | Token::Float(_)) => { *field = token_to_i32(value); return Ok(()); }
+            (ExVar::Float(field), Token::Int(_) | Token::Float(_)) => { *field = token_to_float(value); return Ok(()); }
+            (ExVar::Bool(field), Token::Bool(_) | Token::Int(_)) => { *field = token_to_bool(value); return Ok(()); }
+            (ExVar::String(field), Token::String(text)) => { *field = text.clone(); return Ok(()); }
+            (ExVar::Vector2(field), _) if is_numbers(value, 2) => { *field = token_to_vector2(value); return Ok(()); }
+            (ExVar::Vector3(field), _) if is_numbers(value, 3) => { *field = token_to_vector3(value); return Ok(()); }
+            (ExVar::Quaternion(field), _) if is_numbers(value, 3) => { *field = token_to_quaternion(value); return Ok(()); }
+            (ExVar::Color(field), _) if is_numbers(value, 3) || is_numbers(value, 4) => { *field = token_to_color(value); return Ok(()); }
+            (ExVar::Rectangle(field), _) if is_numbers(value, 4) => { *field = token_to_rectangle(value); return Ok(()); }
+            (ExVar::Int(_), _) => "a whole number",
+            (ExVar::Float(_), _) => "a number",
+            (ExVar::Bool(_), _) => "true or false",
+            (ExVar::String(_), _) => "a string",
+            (ExVar::Vector2(_), _) => "[x, y]",
+            (ExVar::Vector3(_), _) => "[x, y, z]",
+            (ExVar::Quaternion(_), _) => "[x, y, z] in degrees",
+            (ExVar::Color(_), _) => "[r, g, b] or [r, g, b, a]",
+            (ExVar::Rectangle(_), _) => "[x, y, width, height]",
+        };
+
+        Err(format!("expects {}, not {:?}", expected, value))
+    }
 }
 
-// DrythonExRef implementations
-impl DrythonExRef for Object
+fn ex_var<'a>(name: &str, var: ExVar<'a>) -> (String, ExVar<'a>)
 {
+    (name.to_string(), var)
+}
+
+// Adds the prefix to the names of another type's variables, such as a transform's.
+fn prefixed<'a>(prefix: &str, vars: Vec<(String, ExVar<'a>)>) -> impl Iterator<Item = (String, ExVar<'a>)>
+{
+    let prefix = prefix.to_string();
+    vars.into_iter().map(move |(name, var)| (format!("{}{}", prefix, name), var))
+}
+
+pub trait DrythonExRef
+{
+    // Every variable scripts can use, by the name scripts know it as.
+    fn ex_vars(&mut self) -> Vec<(String, ExVar<'_>)>
+    {
+        vec![]
+    }
+
     fn get_drython_vars(&mut self) -> ExVarMap
     {
-        HashMap::from([
-            make_ex_var_map("object.name", Token::String(self.get_name())),
-        ])
+        self.ex_vars().into_iter().map(|(name, var)| (name, var.to_token())).collect()
     }
 
     fn set_my_vars(&mut self, runner: &mut Runner, identifiers: &str)
     {
-        runner.update_variable((format!("{}object.name", identifiers).as_str(), &mut self.name));
+        for (name, var) in self.ex_vars()
+        {
+            var.update(runner, &format!("{}{}", identifiers, name));
+        }
+    }
+
+    // Sets a variable by the name scripts know it as.
+    fn set_drython_var(&mut self, name: &str, value: &Token) -> Result<(), String>
+    {
+        match self.ex_vars().into_iter().find(|(var_name, _)| var_name == name)
+        {
+            Some((_, var)) => var.set(value).map_err(|error| format!("{} {}", name, error)),
+            None => Err(format!("There is no variable {}", name)),
+        }
+    }
+}
+
+// DrythonExRef implementations
+impl DrythonExRef for Object
+{
+    fn ex_vars(&mut self) -> Vec<(String, ExVar<'_>)>
+    {
+        vec![
+            ex_var("object.name", ExVar::String(&mut self.name)),
+        ]
     }
 }
 
 impl DrythonExRef for Object2D
 {
-    fn get_drython_vars(&mut self) -> ExVarMap
+    fn ex_vars(&mut self) -> Vec<(String, ExVar<'_>)>
     {
-        let mut map = HashMap::from([
-        ]);
-        map.extend(self.transform.get_drython_vars().into_iter()
-            .map(|x| (format!("object.{}", x.0), x.1)));
-        map.extend(self.object.get_drython_vars());
+        let mut vars = vec![
+            ex_var("object.origin", ExVar::Vector2(&mut self.origin)),
+            ex_var("object.tint", ExVar::Color(&mut self.tint)),
+            ex_var("object.flip_x", ExVar::Bool(&mut self.flip_x)),
+            ex_var("object.flip_y", ExVar::Bool(&mut self.flip_y)),
+            ex_var("object.source", ExVar::Rectangle(&mut self.source_rect)),
+            ex_var("object.visible", ExVar::Bool(&mut self.visible)),
+            ex_var("object.z_index", ExVar::Int(&mut self.z_index)),
+            ex_var("object.text", ExVar::String(&mut self.text)),
+            ex_var("object.font_size", ExVar::Int(&mut self.font_size)),
+        ];
+        vars.extend(prefixed("object.", self.transform.ex_vars()));
+        vars.extend(self.object.ex_vars());
 
-        map
+        vars
     }
+}
 
-    fn set_my_vars(&mut self, runner: &mut Runner, identifiers: &str)
+impl DrythonExRef for Object3D
+{
+    fn ex_vars(&mut self) -> Vec<(String, ExVar<'_>)>
+    {
+        let mut vars = vec![
+            ex_var("object.tint", ExVar::Color(&mut self.tint)),
+            ex_var("object.visible", ExVar::Bool(&mut self.visible)),
+        ];
+        vars.extend(prefixed("object.", self.transform.ex_vars()));
+        vars.extend(self.object.ex_vars());
+
+        vars
+    }
+}
+
+impl DrythonExRef for Camera3D
+{
+    fn ex_vars(&mut self) -> Vec<(String, ExVar<'_>)>
+    {
+        let mut vars = vec![
+            ex_var("object.position", ExVar::Vector3(&mut self.position)),
+            ex_var("object.target", ExVar::Vector3(&mut self.target)),
+            ex_var("object.up", ExVar::Vector3(&mut self.up)),
+            ex_var("object.fovy", ExVar::Float(&mut self.fovy)),
+            ex_var("object.active", ExVar::Bool(&mut self.active)),
+        ];
+        vars.extend(self.object.ex_vars());
+
+        vars
+    }
+}
+
+impl DrythonExRef for Transform3D
+{
+    fn ex_vars(&mut self) -> Vec<(String, ExVar<'_>)>
     {
-        self.object.set_my_vars(runner, identifiers);
-        self.transform.set_my_vars(runner, format!("{}object.", identifiers).as_str());
+        vec![
+            ex_var("transform.pos", ExVar::Vector3(&mut self.pos)),
+            ex_var("transform.rot", ExVar::Quaternion(&mut self.rot)),
+            ex_var("transform.scale", ExVar::Vector3(&mut self.scale)),
+        ]
+    }
+}
+
+impl DrythonExRef for Camera2D
+{
+    fn ex_vars(&mut self) -> Vec<(String, ExVar<'_>)>
+    {
+        let mut vars = vec![
+            ex_var("object.offset", ExVar::Vector2(&mut self.offset)),
+            ex_var("object.target", ExVar::Vector2(&mut self.target)),
+            ex_var("object.rotation", ExVar::Float(&mut self.rotation)),
+            ex_var("object.zoom", ExVar::Float(&mut self.zoom)),
+            ex_var("object.active", ExVar::Bool(&mut self.active)),
+            ex_var("object.follow", ExVar::String(&mut self.follow)),
+        ];
+        vars.extend(self.object.ex_vars());
+
+        vars
     }
 }
 
 impl DrythonExRef for Transform2D
 {
-    fn get_drython_vars(&mut self) -> ExVarMap
+    fn ex_vars(&mut self) -> Vec<(String, ExVar<'_>)>
+    {
+        vec![
+            ex_var("transform.pos", ExVar::Vector2(&mut self.pos)),
+            ex_var("transform.rot", ExVar::Float(&mut self.rot)),
+            ex_var("transform.scale", ExVar::Vector2(&mut self.scale)),
+        ]
+    }
+}
+
+// Macro for external functions that are not tied to an object.
+// The body is the function's result.
+macro_rules! create_global_function
+{
+    ($name: ident, $args: ident, $body: block) =>
+    {
+        pub fn $name(_: Option<*mut dyn ExFnRef>, $args: Vec<Token>) -> Result<Option<Token>, String>
+        $body
+    };
+}
+
+// Registers every function that scripts can call regardless of their object.
+pub fn register_global_functions(runner: &mut Runner)
+{
+    runner.register_external_function("set_time_scale", None, Box::new(set_time_scale));
+    runner.register_external_function("get_time_scale", None, Box::new(get_time_scale));
+    runner.register_external_function("change_scene", None, Box::new(change_scene));
+    runner.register_external_function("push_scene", None, Box::new(push_scene));
+    runner.register_external_function("overlay_scene", None, Box::new(overlay_scene));
+    runner.register_external_function("pop_scene", None, Box::new(pop_scene));
+    runner.register_external_function("screen_to_world", None, Box::new(screen_to_world));
+    runner.register_external_function("world_to_screen", None, Box::new(world_to_screen));
+    runner.register_external_function("shake_camera", None, Box::new(shake_camera));
+
+    runner.register_external_function("is_key_down", None, Box::new(is_key_down));
+    runner.register_external_function("is_key_pressed", None, Box::new(is_key_pressed));
+    runner.register_external_function("is_key_released", None, Box::new(is_key_released));
+    runner.register_external_function("mouse_position", None, Box::new(mouse_position));
+    runner.register_external_function("is_mouse_button_down", None, Box::new(is_mouse_button_down));
+    runner.register_external_function("is_mouse_button_pressed", None, Box::new(is_mouse_button_pressed));
+    runner.register_external_function("is_mouse_button_released", None, Box::new(is_mouse_button_released));
+    runner.register_external_function("mouse_wheel", None, Box::new(mouse_wheel));
+    runner.register_external_function("is_gamepad_available", None, Box::new(is_gamepad_available));
+    runner.register_external_function("is_gamepad_button_down", None, Box::new(is_gamepad_button_down));
+    runner.register_external_function("is_gamepad_button_pressed", None, Box::new(is_gamepad_button_pressed));
+    runner.register_external_function("is_gamepad_button_released", None, Box::new(is_gamepad_button_released));
+    runner.register_external_function("gamepad_axis", None, Box::new(gamepad_axis));
+
+    runner.register_external_function("is_action_down", None, Box::new(is_action_down));
+    runner.register_external_function("is_action_pressed", None, Box::new(is_action_pressed));
+    runner.register_external_function("is_action_released", None, Box::new(is_action_released));
+    runner.register_external_function("bind_action", None, Box::new(bind_action));
+    runner.register_external_function("clear_action", None, Box::new(clear_action));
+    runner.register_external_function("save_bindings", None, Box::new(save_bindings));
+
+    runner.register_external_function("random", None, Box::new(random));
+    runner.register_external_function("random_range", None, Box::new(random_range));
+    runner.register_external_function("random_int", None, Box::new(random_int));
+    runner.register_external_function("set_seed", None, Box::new(set_seed));
+
+    runner.register_external_function("save_value", None, Box::new(save_value));
+    runner.register_external_function("load_value", None, Box::new(load_value));
+    runner.register_external_function("save_slot", None, Box::new(save_slot));
+
+    runner.register_external_function("find_object", None, Box::new(find_object));
+    runner.register_external_function("find_objects_with_tag", None, Box::new(find_objects_with_tag));
+    runner.register_external_function("find_objects_in_group", None, Box::new(find_objects_in_group));
+    runner.register_external_function("object_exists", None, Box::new(object_exists));
+    runner.register_external_function("get_var", None, Box::new(get_var));
+    runner.register_external_function("set_var", None, Box::new(set_var));
+    runner.register_external_function("call_object", None, Box::new(call_object));
+}
+
+create_global_function!(set_time_scale, args,
+{
+    match args.first().and_then(token_to_f32)
+    {
+        Some(scale) => time::set_time_scale(scale).map(|_| None),
+        None => Err("set_time_scale expects a number.".to_string()),
+    }
+});
+
+create_global_function!(get_time_scale, _args,
+{
+    Ok(Some(Token::Float(time::time_scale())))
+});
+
+create_global_function!(change_scene, args,
+{
+    match args.first()
+    {
+        Some(Token::String(scene_path)) => { request_scene(SceneRequest::Change(scene_path.clone())); Ok(None) }
+        _ => Err("change_scene expects the scene file name.".to_string()),
+    }
+});
+
+create_global_function!(push_scene, args,
+{
+    match args.first()
+    {
+        Some(Token::String(scene_path)) => { request_scene(SceneRequest::Push(scene_path.clone())); Ok(None) }
+        _ => Err("push_scene expects the scene file name.".to_string()),
+    }
+});
+
+create_global_function!(overlay_scene, args,
+{
+    match args.first()
+    {
+        Some(Token::String(scene_path)) => { request_scene(SceneRequest::Overlay(scene_path.clone())); Ok(None) }
+        _ => Err("overlay_scene expects the scene file name.".to_string()),
+    }
+});
+
+create_global_function!(pop_scene, _args,
+{
+    request_scene(SceneRequest::Pop);
+    Ok(None)
+});
+
+// Without an active camera, world and screen space are the same.
+create_global_function!(screen_to_world, args,
+{
+    let screen = token_to_vector2(args.first().ok_or("screen_to_world expects a position.")?);
+    let world = active_view().map(|view| view.screen_to_world(screen)).unwrap_or(screen);
+    Ok(Some(vector2_to_token(world)))
+});
+
+create_global_function!(world_to_screen, args,
+{
+    let world = token_to_vector2(args.first().ok_or("world_to_screen expects a position.")?);
+    let screen = active_view().map(|view| view.world_to_screen(world)).unwrap_or(world);
+    Ok(Some(vector2_to_token(screen)))
+});
+
+create_global_function!(shake_camera, args,
+{
+    match (args.first().and_then(token_to_f32), args.get(1).and_then(token_to_f32))
+    {
+        (Some(intensity), Some(duration)) => { request_shake(intensity, duration); Ok(None) }
+        _ => Err("shake_camera expects an intensity and a duration.".to_string()),
+    }
+});
+
+create_global_function!(random, _args,
+{
+    Ok(Some(Token::Float(random::next_f32())))
+});
+
+create_global_function!(random_range, args,
+{
+    match (args.first().and_then(token_to_f32), args.get(1).and_then(token_to_f32))
+    {
+        (Some(min), Some(max)) => Ok(Some(Token::Float(random::range_f32(min, max)))),
+        _ => Err("random_range expects a minimum and a maximum.".to_string()),
+    }
+});
+
+create_global_function!(random_int, args,
+{
+    match (args.first(), args.get(1))
+    {
+        (Some(Token::Int(min)), Some(Token::Int(max))) => Ok(Some(Token::Int(random::range_i32(*min, *max)))),
+        _ => Err("random_int expects a whole minimum and maximum.".to_string()),
+    }
+});
+
+create_global_function!(set_seed, args,
+{
+    match args.first()
+    {
+        Some(Token::Int(seed)) => { random::set_seed(*seed as u64); Ok(None) }
+        _ => Err("set_seed expects a whole number.".to_string()),
+    }
+});
+
+create_global_function!(save_value, args,
+{
+    let key = string_arg(&args, 0, "save_value")?;
+    match args.get(1)
+    {
+        Some(value) => { save_data::save_value(key, value)?; Ok(None) }
+        None => Err("save_value expects a key and a value.".to_string()),
+    }
+});
+
+// load_value("high_score", 0) gives the default when nothing is saved under the key.
+create_global_function!(load_value, args,
+{
+    let key = string_arg(&args, 0, "load_value")?;
+    match save_data::load_value(key)
+    {
+        Some(value) => Ok(Some(value)),
+        None => Ok(args.into_iter().nth(1)),
+    }
+});
+
+// Switches to another save slot, with no arguments it gives the current one.
+create_global_function!(save_slot, args,
+{
+    match args.first()
     {
-        HashMap::from([
-            make_ex_var_map("transform.pos", vector2_to_token(self.pos)),
-            make_ex_var_map("transform.rot", Token::Float(self.rot)),
-            make_ex_var_map("transform.scale", vector2_to_token(self.scale)),
-        ])
+        Some(Token::Int(slot)) if *slot >= 0 => { save_data::load_slot(*slot as u32)?; Ok(None) }
+        None => Ok(Some(Token::Int(save_data::slot() as i32))),
+        _ => Err("save_slot expects a whole number of 0 or more.".to_string()),
     }
+});
+
+// Positions are given as [x, y], or [x, y, z] for 3d objects.
+fn position_arg(pos: &Token, function: &str) -> Result<Yaml, String>
+{
+    let values: Vec<f32> = match pos
+    {
+        Token::Collection(pos) => pos.iter().filter_map(token_to_f32).collect(),
+        _ => vec![],
+    };
 
-    fn set_my_vars(&mut self, runner: &mut drython::types::Runner, identifiers: &str)
+    match values.len()
     {
-        runner.update_variable_conversion((format!("{}transform.pos", identifiers).as_str(), &mut self.pos), token_to_vector2);
-        runner.update_variable((format!("{}transform.rot", identifiers).as_str(), &mut self.rot));
-        runner.update_variable_conversion((format!("{}transform.scale", identifiers).as_str(), &mut self.scale), token_to_vector2);
+        2 => Ok(write_vector2(Vector2::new(values[0], values[1]))),
+        3 => Ok(write_vector3(Vector3::new(values[0], values[1], values[2]))),
+        _ => Err(format!("{} expects the position as [x, y] or [x, y, z].", function)),
     }
 }
 
-// Macro for handling external functions to drython.
-macro_rules! create_external_function
+fn ids_to_token(ids: Vec<usize>) -> Token
+{
+    Token::Collection(ids.into_iter().map(|id| Token::Int(id as i32)).collect())
+}
+
+// The id of the first object with the name, or 0 when there is none.
+create_global_function!(find_object, args,
 {
-    ($name: ident, $sub_downcast: tt, $sub_name: ident, $args: ident, $body: block) =>
+    let name = string_arg(&args, 0, "find_object")?;
+    let found = find_objects(|info| &info.name == name);
+    Ok(Some(Token::Int(found.first().copied().unwrap_or(0) as i32)))
+});
+
+create_global_function!(find_objects_with_tag, args,
+{
+    let tag = string_arg(&args, 0, "find_objects_with_tag")?;
+    Ok(Some(ids_to_token(find_objects(|info| info.tags.contains(tag)))))
+});
+
+create_global_function!(find_objects_in_group, args,
+{
+    let group = string_arg(&args, 0, "find_objects_in_group")?;
+    Ok(Some(ids_to_token(find_objects(|info| &info.group == group))))
+});
+
+create_global_function!(object_exists, args,
+{
+    let id = id_arg(&args, "object_exists")?;
+    Ok(Some(bool_to_token(with_object_info(id, |_| ()).is_some())))
+});
+
+// get_var(id, "object.transform.pos") gives the value as it was at the start of the update.
+create_global_function!(get_var, args,
+{
+    let id = id_arg(&args, "get_var")?;
+    let name = string_arg(&args, 1, "get_var")?;
+
+    match with_object_info(id, |info| info.vars.get(name).cloned())
+    {
+        Some(Some(value)) => Ok(Some(value)),
+        Some(None) => Err(format!("Object {} has no variable {}.", id, name)),
+        None => Err(format!("There is no object with the id {}.", id)),
+    }
+});
+
+// set_var(id, "object.visible", false) changes the object once the update finishes.
+create_global_function!(set_var, args,
+{
+    let id = id_arg(&args, "set_var")?;
+    let name = string_arg(&args, 1, "set_var")?.clone();
+
+    match args.into_iter().nth(2)
+    {
+        Some(value) => { request_object(ObjectRequest::SetVar(id, name, value)); Ok(None) }
+        None => Err("set_var expects an object id, a variable name and a value.".to_string()),
+    }
+});
+
+// call_object(id, "take_damage", 10) calls the function in the object's script, with any
+// arguments after its name, once the update finishes.
+create_global_function!(call_object, args,
+{
+    let id = id_arg(&args, "call_object")?;
+    let function = string_arg(&args, 1, "call_object")?.clone();
+
+    request_object(ObjectRequest::Call(id, function, args.into_iter().skip(2).collect()));
+    Ok(None)
+});
+
+fn id_arg(args: &[Token], function: &str) -> Result<usize, String>
+{
+    match args.first()
+    {
+        Some(Token::Int(id)) if *id > 0 => Ok(*id as usize),
+        _ => Err(format!("{} expects an object id as the first argument.", function)),
+    }
+}
+
+fn string_arg<'a>(args: &'a [Token], index: usize, function: &str) -> Result<&'a String, String>
+{
+    match args.get(index)
+    {
+        Some(Token::String(value)) => Ok(value),
+        _ => Err(format!("{} expects a string as argument {}.", function, index + 1)),
+    }
+}
+
+fn gamepad_arg(args: &[Token], function: &str) -> Result<usize, String>
+{
+    match args.first()
+    {
+        Some(Token::Int(gamepad)) if *gamepad >= 0 => Ok(*gamepad as usize),
+        _ => Err(format!("{} expects the gamepad number as the first argument.", function)),
+    }
+}
+
+create_global_function!(is_key_down, args,
+{
+    let key = string_arg(&args, 0, "is_key_down")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_key_down(key)))))
+});
+
+create_global_function!(is_key_pressed, args,
+{
+    let key = string_arg(&args, 0, "is_key_pressed")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_key_pressed(key)))))
+});
+
+create_global_function!(is_key_released, args,
+{
+    let key = string_arg(&args, 0, "is_key_released")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_key_released(key)))))
+});
+
+create_global_function!(mouse_position, _args,
+{
+    let (x, y) = input::with_input(|state| state.current.mouse_position);
+    Ok(Some(vector2_to_token(Vector2::new(x, y))))
+});
+
+create_global_function!(is_mouse_button_down, args,
+{
+    let button = string_arg(&args, 0, "is_mouse_button_down")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_mouse_button_down(button)))))
+});
+
+create_global_function!(is_mouse_button_pressed, args,
+{
+    let button = string_arg(&args, 0, "is_mouse_button_pressed")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_mouse_button_pressed(button)))))
+});
+
+create_global_function!(is_mouse_button_released, args,
+{
+    let button = string_arg(&args, 0, "is_mouse_button_released")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_mouse_button_released(button)))))
+});
+
+create_global_function!(mouse_wheel, _args,
+{
+    Ok(Some(Token::Float(input::with_input(|state| state.current.mouse_wheel))))
+});
+
+create_global_function!(is_gamepad_available, args,
+{
+    let gamepad = gamepad_arg(&args, "is_gamepad_available")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_gamepad_available(gamepad)))))
+});
+
+create_global_function!(is_gamepad_button_down, args,
+{
+    let gamepad = gamepad_arg(&args, "is_gamepad_button_down")?;
+    let button = string_arg(&args, 1, "is_gamepad_button_down")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_gamepad_button_down(gamepad, button)))))
+});
+
+create_global_function!(is_gamepad_button_pressed, args,
+{
+    let gamepad = gamepad_arg(&args, "is_gamepad_button_pressed")?;
+    let button = string_arg(&args, 1, "is_gamepad_button_pressed")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_gamepad_button_pressed(gamepad, button)))))
+});
+
+create_global_function!(is_gamepad_button_released, args,
+{
+    let gamepad = gamepad_arg(&args, "is_gamepad_button_released")?;
+    let button = string_arg(&args, 1, "is_gamepad_button_released")?;
+    Ok(Some(bool_to_token(input::with_input(|state| state.is_gamepad_button_released(gamepad, button)))))
+});
+
+create_global_function!(gamepad_axis, args,
+{
+    let gamepad = gamepad_arg(&args, "gamepad_axis")?;
+    let axis = string_arg(&args, 1, "gamepad_axis")?;
+    Ok(Some(Token::Float(input::with_input(|state| state.gamepad_axis(gamepad, axis)))))
+});
+
+// Macro for external functions that work on any object type.
+macro_rules! create_object_function
+{
+    ($name: ident, $object: ident, $args: ident, $body: block) =>
     {
-        
         pub fn $name(optional_object: Option<*mut dyn ExFnRef>, $args: Vec<Token>) -> Result<Option<Token>, String>
         {
             if let Some(object_ref) = optional_object
             {
                 unsafe
                 {
-                    if let Some($sub_name) = (*object_ref.as_mut().unwrap()).as_any_mut().downcast_mut::<Box<dyn TObject>>().unwrap().downcast_mut::<$sub_downcast>()
+                    if let Some($object) = (*object_ref.as_mut().unwrap()).as_any_mut().downcast_mut::<Box<dyn TObject>>()
                     {
-                        $body
+                        return $body;
                     }
                 }
             }
-            else
-            {
-                return Err("Could not track optional object.".to_string());
-            }
 
-            Ok(None)
+            Err("Could not track optional object.".to_string())
         }
     };
 }
 
+create_global_function!(is_action_down, args,
+{
+    let action = string_arg(&args, 0, "is_action_down")?;
+    Ok(Some(bool_to_token(input::is_action_down(action))))
+});
+
+create_global_function!(is_action_pressed, args,
+{
+    let action = string_arg(&args, 0, "is_action_pressed")?;
+    Ok(Some(bool_to_token(input::is_action_pressed(action))))
+});
+
+create_global_function!(is_action_released, args,
+{
+    let action = string_arg(&args, 0, "is_action_released")?;
+    Ok(Some(bool_to_token(input::is_action_released(action))))
+});
+
+// bind_action("jump", "keys", "space"), the kind can be keys, mouse or gamepad.
+create_global_function!(bind_action, args,
+{
+    let action = string_arg(&args, 0, "bind_action")?;
+    let kind = string_arg(&args, 1, "bind_action")?;
+    let name = string_arg(&args, 2, "bind_action")?;
+
+    match Binding::new(kind, name)
+    {
+        Some(binding) => { input::with_actions(|actions| actions.bind(action, binding)); Ok(None) }
+        None => Err(format!("{} is not a valid {} binding.", name, kind)),
+    }
+});
+
+create_global_function!(clear_action, args,
+{
+    let action = string_arg(&args, 0, "clear_action")?;
+    input::with_actions(|actions| actions.clear(action));
+    Ok(None)
+});
+
+create_global_function!(save_bindings, _args,
+{
+    let path = input::bindings_path();
+    if path.is_empty()
+    {
+        return Err("No input_bindings file is set in the game yaml.".to_string());
+    }
+
+    input::with_actions(|actions| actions.save(&path))?;
+    Ok(None)
+});
+
 impl Object
 {
-    // The object can send of events when an input is registered.
-    create_external_function!(register_input, Object2D, object2d, args,
+    // The object calls the named function in its script whenever the action is pressed
+    // or released, with true when pressed.
+    create_object_function!(register_input, object, args,
     {
-        object2d.transform.pos = Vector2::new(-50.0, 20.0);
-        println!("args1: {:?}", args[0]);
+        let action = string_arg(&args, 0, "register_input")?;
+        let function = string_arg(&args, 1, "register_input")?;
+
+        let input = (action.clone(), function.clone());
+        if !object.get_obj().inputs.contains(&input)
+        {
+            object.get_obj().inputs.push(input);
+        }
+
+        Ok(None)
     });
+}
+
+// destroy() removes the calling object, destroy(id) any other, once the update finishes.
+create_object_function!(destroy, object, args,
+{
+    match args.first()
+    {
+        Some(Token::Int(id)) if *id > 0 => request_destroy(*id as usize),
+        None => request_destroy(object.get_id()),
+        _ => return Err("destroy expects an object id, or nothing to destroy itself.".to_string()),
+    }
+    Ok(None)
+});
+
+// instantiate("enemy.yaml", [x, y]) adds the prefab to the calling object's scene after
+// this update. The position is optional.
+create_object_function!(instantiate, object, args,
+{
+    let prefab = string_arg(&args, 0, "instantiate")?;
+
+    let mut properties = Hash::new();
+    properties.insert(Yaml::String("prefab".to_string()), Yaml::String(prefab.clone()));
+    if let Some(pos) = args.get(1)
+    {
+        properties.insert(Yaml::String("pos".to_string()), position_arg(pos, "instantiate")?);
+    }
+
+    request_spawn(object.get_id(), Yaml::Hash(properties));
+    Ok(None)
+});
+
+// spawn("object2d", [x, y], "bullet.dry") adds an object of the type to the calling object's
+// scene after this update. The position and script are optional.
+create_object_function!(spawn, object, args,
+{
+    let type_name = string_arg(&args, 0, "spawn")?;
+
+    let mut properties = Hash::new();
+    properties.insert(Yaml::String("type".to_string()), Yaml::String(type_name.clone()));
+    if let Some(pos) = args.get(1)
+    {
+        properties.insert(Yaml::String("pos".to_string()), position_arg(pos, "spawn")?);
+    }
+    if args.len() > 2
+    {
+        properties.insert(Yaml::String("script".to_string()), Yaml::String(string_arg(&args, 2, "spawn")?.clone()));
+    }
+
+    request_spawn(object.get_id(), Yaml::Hash(properties));
+    Ok(None)
+});
+
+// Joins the arguments into one message, strings are written without quotes.
+fn token_to_message(token: &Token) -> String
+{
+    match token
+    {
+        Token::String(value) => value.clone(),
+        Token::Int(value) => value.to_string(),
+        Token::Float(value) => value.to_string(),
+        Token::Bool(value) => value.to_string(),
+        Token::Collection(values) => format!("[{}]", values.iter().map(token_to_message).collect::<Vec<String>>().join(", ")),
+        other => format!("{:?}", other),
+    }
+}
+
+fn log_from_script(level: Level, script: &str, object: &mut Box<dyn TObject>, args: &[Token])
+{
+    let message = args.iter().map(token_to_message).collect::<Vec<String>>().join(" ");
+    logging::log(level, "script", &format!("[{}] {}: {}", script, object.get_name(), message));
+}
+
+// Makes log_info, log_warn or log_error for one of the object's scripts, so each message
+// is tagged with the script that logged it.
+pub fn script_logger(level: Level, script: String) -> impl Fn(Option<*mut dyn ExFnRef>, Vec<Token>) -> Result<Option<Token>, String>
+{
+    move |optional_object, args|
+    {
+        if let Some(object_ref) = optional_object
+        {
+            unsafe
+            {
+                if let Some(object) = (*object_ref.as_mut().unwrap()).as_any_mut().downcast_mut::<Box<dyn TObject>>()
+                {
+                    log_from_script(level, &script, object, &args);
+                    return Ok(None);
+                }
+            }
+        }
+
+        Err("Could not track optional object.".to_string())
+    }
+}
+
+// Object ids start at 1, so 0 is used when there is no object.
+create_object_function!(get_parent, object, _args,
+{
+    Ok(Some(Token::Int(object.get_obj().parent.unwrap_or(0) as i32)))
+});
+
+create_object_function!(get_children, object, _args,
+{
+    Ok(Some(Token::Collection(object.get_obj().children.iter().map(|id| Token::Int(*id as i32)).collect())))
+});
+
+create_object_function!(find_child, object, args,
+{
+    let child_name = match args.first()
+    {
+        Some(Token::String(child_name)) => child_name,
+        _ => return Err("find_child expects the name of the child.".to_string()),
+    };
+
+    let found = object.get_obj().children.iter()
+        .find(|id| with_object_info(**id, |info| &info.name == child_name).unwrap_or(false));
+
+    Ok(Some(Token::Int(found.copied().unwrap_or(0) as i32)))
+});
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn transform() -> Transform2D
+    {
+        Transform2D { pos: Vector2::zero(), rot: 0.0, scale: Vector2::one() }
+    }
+
+    #[test]
+    fn set_accepts_ints_for_floats()
+    {
+        let mut transform = transform();
+        transform.set_drython_var("transform.rot", &Token::Int(90)).unwrap();
+        transform.set_drython_var("transform.pos", &Token::Collection(vec![Token::Int(1), Token::Float(2.5)])).unwrap();
+
+        assert_eq!(transform.rot, 90.0);
+        assert_eq!(transform.pos, Vector2::new(1.0, 2.5));
+    }
 
+    #[test]
+    fn set_reports_wrong_types_and_names()
+    {
+        let mut transform = transform();
+        let wrong_type = transform.set_drython_var("transform.rot", &Token::String("up".to_string())).unwrap_err();
+        assert!(wrong_type.contains("expects a number"));
+        assert!(transform.set_drython_var("transform.pos", &Token::Collection(vec![Token::Int(1)])).is_err());
+        assert!(transform.set_drython_var("transform.size", &Token::Int(1)).unwrap_err().contains("no variable"));
+        assert_eq!(transform.rot, 0.0);
+    }
+
+    #[test]
+    fn vars_match_what_set_accepts()
+    {
+        let mut transform = transform();
+        transform.rot = 45.0;
+        let vars = transform.get_drython_vars();
+
+        let mut copy = self::transform();
+        for (name, value) in &vars
+        {
+            copy.set_drython_var(name, value).unwrap();
+        }
+        assert_eq!(copy.rot, 45.0);
+        assert_eq!(copy.scale, Vector2::one());
+    }
+
+    fn assert_same_rotation(a: Quaternion, b: Quaternion)
+    {
+        // q and -q are the same rotation.
+        let dot = a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
+        assert!((dot.abs() - 1.0).abs() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn quaternions_round_trip_through_tokens()
+    {
+        let rotations = [
+            Quaternion::identity(),
+            Quaternion::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), 1.0),
+            Quaternion::from_axis_angle(Vector3::new(1.0, 2.0, 3.0).normalized(), 2.5),
+            Quaternion::from_euler(0.3_f32, -0.7, 1.9),
+        ];
+
+        for rot in rotations.iter()
+        {
+            assert_same_rotation(token_to_quaternion(&quaternion_to_token(*rot)), *rot);
+        }
+    }
 
-    pub fn get_parent()
+    #[test]
+    fn quaternion_tokens_are_in_degrees()
     {
+        let quarter_turn = Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), std::f32::consts::FRAC_PI_2);
+        let euler = token_to_vector3(&quaternion_to_token(quarter_turn));
 
+        assert!(euler.x.abs() < 1e-3);
+        assert!(euler.y.abs() < 1e-3);
+        assert!((euler.z - 90.0).abs() < 1e-3);
     }
 }
@@ -0,0 +1,285 @@
+use crate::log_warn;
+use std::time::Duration;
+use yaml_rust::Yaml;
+use crate::script_error::ErrorPolicy;
+use crate::logging::LogConfig;
+use crate::time::MAX_TIME_SCALE;
+
+// Most fixed updates per second, past which a fixed step would be too short to matter.
+pub const MAX_FIXED_UPDATE_RATE: f32 = 1000.0;
+
+pub struct GameConfig
+{
+    pub window_width: i32,
+    pub window_height: i32,
+    pub fullscreen: bool,
+    pub resizable: bool,
+    pub vsync: bool,
+    // 0 leaves the frame rate uncapped.
+    pub target_fps: u32,
+
+    // Fixed updates per second.
+    pub fixed_update_rate: f32,
+    // Most fixed updates run in a single frame before the rest are dropped.
+    pub max_catch_up_ticks: u32,
+    pub time_scale: f32,
+
+    // Random seed for scripts, picked from the time when not set.
+    pub seed: Option<u64>,
+    // Files to record the input of every fixed update to, or to play it back from.
+    pub record_input: String,
+    pub replay_input: String,
+
+    pub logging: LogConfig,
+
+    // What happens to scripts that fail.
+    pub script_errors: ErrorPolicy,
+
+    // Checks for changed asset files every interval, in seconds, and reloads them.
+    pub hot_reload: bool,
+    pub hot_reload_interval: f32,
+}
+
+impl GameConfig
+{
+    pub fn new() -> Self
+    {
+        GameConfig
+        {
+            window_width: 640,
+            window_height: 480,
+            fullscreen: false,
+            resizable: false,
+            vsync: false,
+            target_fps: 60,
+
+            fixed_update_rate: 30.0,
+            max_catch_up_ticks: 5,
+            time_scale: 1.0,
+
+            seed: None,
+            record_input: String::new(),
+            replay_input: String::new(),
+
+            logging: LogConfig::new(),
+
+            script_errors: ErrorPolicy::Disable,
+
+            hot_reload: false,
+            hot_reload_interval: 0.5,
+        }
+    }
+
+    // Reads the window and timing sections of the game yaml.
+    // Missing values keep their defaults, invalid ones are reported and ignored.
+    pub fn from_yaml(contents: &Yaml) -> Self
+    {
+        let mut config = GameConfig::new();
+
+        let window = &contents["window"];
+        if let Some(width) = GameConfig::read_positive(&window["width"], "window.width")
+        {
+            config.window_width = width as i32;
+        }
+        if let Some(height) = GameConfig::read_positive(&window["height"], "window.height")
+        {
+            config.window_height = height as i32;
+        }
+        if let Some(fullscreen) = GameConfig::read_bool(&window["fullscreen"], "window.fullscreen")
+        {
+            config.fullscreen = fullscreen;
+        }
+        if let Some(resizable) = GameConfig::read_bool(&window["resizable"], "window.resizable")
+        {
+            config.resizable = resizable;
+        }
+        if let Some(vsync) = GameConfig::read_bool(&window["vsync"], "window.vsync")
+        {
+            config.vsync = vsync;
+        }
+        match &window["target_fps"]
+        {
+            Yaml::Integer(fps) if *fps >= 0 => { config.target_fps = *fps as u32; }
+            Yaml::BadValue => (),
+            other => log_warn!("config", "Invalid value {:?} for window.target_fps, expected a whole number of 0 or more.", other),
+        }
+
+        let timing = &contents["timing"];
+        if let Some(rate) = GameConfig::read_positive(&timing["fixed_update_rate"], "timing.fixed_update_rate")
+        {
+            if rate > MAX_FIXED_UPDATE_RATE as f64
+            {
+                log_warn!("config", "timing.fixed_update_rate {} is above the most of {}, using {}.", rate, MAX_FIXED_UPDATE_RATE, MAX_FIXED_UPDATE_RATE);
+            }
+            config.fixed_update_rate = (rate as f32).min(MAX_FIXED_UPDATE_RATE);
+        }
+        if let Some(ticks) = GameConfig::read_positive(&timing["max_catch_up_ticks"], "timing.max_catch_up_ticks")
+        {
+            config.max_catch_up_ticks = ticks as u32;
+        }
+        match GameConfig::read_number(&timing["time_scale"])
+        {
+            Some(scale) if scale.is_finite() && scale >= 0.0 => { config.time_scale = (scale as f32).min(MAX_TIME_SCALE); }
+            Some(scale) => log_warn!("config", "Invalid value {} for timing.time_scale, it must be a number of 0 or more.", scale),
+            None => if !timing["time_scale"].is_badvalue()
+            {
+                log_warn!("config", "Invalid value {:?} for timing.time_scale, expected a number.", timing["time_scale"]);
+            }
+        }
+
+        config.logging = LogConfig::from_yaml(&contents["logging"]);
+
+        let debug = &contents["debug"];
+        match &debug["seed"]
+        {
+            Yaml::Integer(seed) if *seed >= 0 => { config.seed = Some(*seed as u64); }
+            Yaml::BadValue => (),
+            other => log_warn!("config", "Invalid value {:?} for debug.seed, expected a whole number of 0 or more.", other),
+        }
+        config.record_input = debug["record_input"].as_str().unwrap_or("").to_string();
+        config.replay_input = debug["replay_input"].as_str().unwrap_or("").to_string();
+        match &debug["script_errors"]
+        {
+            Yaml::String(name) => match ErrorPolicy::from_name(name)
+            {
+                Some(policy) => { config.script_errors = policy; }
+                None => log_warn!("config", "Invalid value {:?} for debug.script_errors, expected disable, keep_running or halt.", name),
+            },
+            Yaml::BadValue => (),
+            other => log_warn!("config", "Invalid value {:?} for debug.script_errors, expected disable, keep_running or halt.", other),
+        }
+        if let Some(hot_reload) = GameConfig::read_bool(&debug["hot_reload"], "debug.hot_reload")
+        {
+            config.hot_reload = hot_reload;
+        }
+        if let Some(interval) = GameConfig::read_positive(&debug["hot_reload_interval"], "debug.hot_reload_interval")
+        {
+            config.hot_reload_interval = interval as f32;
+        }
+
+        config
+    }
+
+    // The rate can also come from a replay file, so it is checked again here.
+    // Invalid rates fall back to the default one.
+    pub fn fixed_step(&self) -> Duration
+    {
+        let rate = if self.fixed_update_rate.is_finite() && self.fixed_update_rate > 0.0
+        {
+            self.fixed_update_rate.min(MAX_FIXED_UPDATE_RATE)
+        }
+        else { GameConfig::new().fixed_update_rate };
+
+        Duration::from_secs_f32(1.0 / rate)
+    }
+
+    fn read_number(value: &Yaml) -> Option<f64>
+    {
+        match value
+        {
+            Yaml::Integer(i) => Some(*i as f64),
+            Yaml::Real(_) => value.as_f64(),
+            _ => None,
+        }
+    }
+
+    fn read_positive(value: &Yaml, key: &str) -> Option<f64>
+    {
+        match GameConfig::read_number(value)
+        {
+            Some(number) if number.is_finite() && number > 0.0 => Some(number),
+            Some(number) =>
+            {
+                log_warn!("config", "Invalid value {} for {}, it must be a number greater than 0.", number, key);
+                None
+            }
+            None =>
+            {
+                if !value.is_badvalue()
+                {
+                    log_warn!("config", "Invalid value {:?} for {}, expected a number.", value, key);
+                }
+                None
+            }
+        }
+    }
+
+    fn read_bool(value: &Yaml, key: &str) -> Option<bool>
+    {
+        match value
+        {
+            Yaml::Boolean(b) => Some(*b),
+            Yaml::BadValue => None,
+            other =>
+            {
+                log_warn!("config", "Invalid value {:?} for {}, expected true or false.", other, key);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    fn config(source: &str) -> GameConfig
+    {
+        GameConfig::from_yaml(&YamlLoader::load_from_str(source).unwrap()[0])
+    }
+
+    #[test]
+    fn missing_values_keep_defaults()
+    {
+        let config = config("name: test");
+        assert_eq!(config.window_width, 640);
+        assert_eq!(config.fixed_update_rate, 30.0);
+        assert_eq!(config.time_scale, 1.0);
+        assert_eq!(config.script_errors, ErrorPolicy::Disable);
+    }
+
+    #[test]
+    fn reads_valid_values()
+    {
+        let config = config("window:\n  width: 800\n  vsync: true\ntiming:\n  fixed_update_rate: 60\n  time_scale: 0.5\ndebug:\n  seed: 7\n  script_errors: halt");
+        assert_eq!(config.window_width, 800);
+        assert!(config.vsync);
+        assert_eq!(config.fixed_update_rate, 60.0);
+        assert_eq!(config.time_scale, 0.5);
+        assert_eq!(config.seed, Some(7));
+        assert_eq!(config.script_errors, ErrorPolicy::Halt);
+    }
+
+    #[test]
+    fn ignores_invalid_values()
+    {
+        let config = config("window:\n  width: -5\n  vsync: maybe\ntiming:\n  fixed_update_rate: .inf\n  time_scale: -1\ndebug:\n  seed: -3\n  script_errors: explode");
+        assert_eq!(config.window_width, 640);
+        assert!(!config.vsync);
+        assert_eq!(config.fixed_update_rate, 30.0);
+        assert_eq!(config.time_scale, 1.0);
+        assert_eq!(config.seed, None);
+        assert_eq!(config.script_errors, ErrorPolicy::Disable);
+    }
+
+    #[test]
+    fn clamps_large_values()
+    {
+        let config = config("timing:\n  fixed_update_rate: 1000000\n  time_scale: 1000000");
+        assert_eq!(config.fixed_update_rate, MAX_FIXED_UPDATE_RATE);
+        assert_eq!(config.time_scale, MAX_TIME_SCALE);
+    }
+
+    #[test]
+    fn fixed_step_falls_back_on_invalid_rates()
+    {
+        let mut config = GameConfig::new();
+        for rate in [0.0, -10.0, f32::NAN, f32::INFINITY]
+        {
+            config.fixed_update_rate = rate;
+            assert_eq!(config.fixed_step(), Duration::from_secs_f32(1.0 / 30.0));
+        }
+    }
+}
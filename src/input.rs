@@ -0,0 +1,608 @@
+use crate::{log_error, log_warn};
+use raylib::prelude::*;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+use yaml_rust::yaml::Hash;
+
+// Keys, buttons and axes are named the way scripts refer to them.
+const KEYS: [(&str, KeyboardKey); 70] =
+[
+    ("a", KeyboardKey::KEY_A), ("b", KeyboardKey::KEY_B), ("c", KeyboardKey::KEY_C), ("d", KeyboardKey::KEY_D),
+    ("e", KeyboardKey::KEY_E), ("f", KeyboardKey::KEY_F), ("g", KeyboardKey::KEY_G), ("h", KeyboardKey::KEY_H),
+    ("i", KeyboardKey::KEY_I), ("j", KeyboardKey::KEY_J), ("k", KeyboardKey::KEY_K), ("l", KeyboardKey::KEY_L),
+    ("m", KeyboardKey::KEY_M), ("n", KeyboardKey::KEY_N), ("o", KeyboardKey::KEY_O), ("p", KeyboardKey::KEY_P),
+    ("q", KeyboardKey::KEY_Q), ("r", KeyboardKey::KEY_R), ("s", KeyboardKey::KEY_S), ("t", KeyboardKey::KEY_T),
+    ("u", KeyboardKey::KEY_U), ("v", KeyboardKey::KEY_V), ("w", KeyboardKey::KEY_W), ("x", KeyboardKey::KEY_X),
+    ("y", KeyboardKey::KEY_Y), ("z", KeyboardKey::KEY_Z),
+    ("0", KeyboardKey::KEY_ZERO), ("1", KeyboardKey::KEY_ONE), ("2", KeyboardKey::KEY_TWO), ("3", KeyboardKey::KEY_THREE),
+    ("4", KeyboardKey::KEY_FOUR), ("5", KeyboardKey::KEY_FIVE), ("6", KeyboardKey::KEY_SIX), ("7", KeyboardKey::KEY_SEVEN),
+    ("8", KeyboardKey::KEY_EIGHT), ("9", KeyboardKey::KEY_NINE),
+    ("space", KeyboardKey::KEY_SPACE), ("escape", KeyboardKey::KEY_ESCAPE), ("enter", KeyboardKey::KEY_ENTER),
+    ("tab", KeyboardKey::KEY_TAB), ("backspace", KeyboardKey::KEY_BACKSPACE), ("delete", KeyboardKey::KEY_DELETE),
+    ("up", KeyboardKey::KEY_UP), ("down", KeyboardKey::KEY_DOWN), ("left", KeyboardKey::KEY_LEFT), ("right", KeyboardKey::KEY_RIGHT),
+    ("left_shift", KeyboardKey::KEY_LEFT_SHIFT), ("right_shift", KeyboardKey::KEY_RIGHT_SHIFT),
+    ("left_control", KeyboardKey::KEY_LEFT_CONTROL), ("right_control", KeyboardKey::KEY_RIGHT_CONTROL),
+    ("left_alt", KeyboardKey::KEY_LEFT_ALT), ("right_alt", KeyboardKey::KEY_RIGHT_ALT),
+    ("home", KeyboardKey::KEY_HOME), ("end", KeyboardKey::KEY_END),
+    ("page_up", KeyboardKey::KEY_PAGE_UP), ("page_down", KeyboardKey::KEY_PAGE_DOWN),
+    ("f1", KeyboardKey::KEY_F1), ("f2", KeyboardKey::KEY_F2), ("f3", KeyboardKey::KEY_F3), ("f4", KeyboardKey::KEY_F4),
+    ("f5", KeyboardKey::KEY_F5), ("f6", KeyboardKey::KEY_F6), ("f7", KeyboardKey::KEY_F7), ("f8", KeyboardKey::KEY_F8),
+    ("f9", KeyboardKey::KEY_F9), ("f10", KeyboardKey::KEY_F10), ("f11", KeyboardKey::KEY_F11), ("f12", KeyboardKey::KEY_F12),
+    ("insert", KeyboardKey::KEY_INSERT), ("caps_lock", KeyboardKey::KEY_CAPS_LOCK),
+];
+
+const MOUSE_BUTTONS: [(&str, MouseButton); 3] =
+[
+    ("left", MouseButton::MOUSE_LEFT_BUTTON),
+    ("right", MouseButton::MOUSE_RIGHT_BUTTON),
+    ("middle", MouseButton::MOUSE_MIDDLE_BUTTON),
+];
+
+const GAMEPAD_BUTTONS: [(&str, GamepadButton); 15] =
+[
+    ("a", GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN),
+    ("b", GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT),
+    ("x", GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_LEFT),
+    ("y", GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP),
+    ("dpad_up", GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP),
+    ("dpad_down", GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN),
+    ("dpad_left", GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT),
+    ("dpad_right", GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT),
+    ("left_bumper", GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_1),
+    ("right_bumper", GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_1),
+    ("left_stick", GamepadButton::GAMEPAD_BUTTON_LEFT_THUMB),
+    ("right_stick", GamepadButton::GAMEPAD_BUTTON_RIGHT_THUMB),
+    ("select", GamepadButton::GAMEPAD_BUTTON_MIDDLE_LEFT),
+    ("home", GamepadButton::GAMEPAD_BUTTON_MIDDLE),
+    ("start", GamepadButton::GAMEPAD_BUTTON_MIDDLE_RIGHT),
+];
+
+const GAMEPAD_AXES: [(&str, GamepadAxis); 6] =
+[
+    ("left_x", GamepadAxis::GAMEPAD_AXIS_LEFT_X),
+    ("left_y", GamepadAxis::GAMEPAD_AXIS_LEFT_Y),
+    ("right_x", GamepadAxis::GAMEPAD_AXIS_RIGHT_X),
+    ("right_y", GamepadAxis::GAMEPAD_AXIS_RIGHT_Y),
+    ("left_trigger", GamepadAxis::GAMEPAD_AXIS_LEFT_TRIGGER),
+    ("right_trigger", GamepadAxis::GAMEPAD_AXIS_RIGHT_TRIGGER),
+];
+
+pub const MAX_GAMEPADS: i32 = 4;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GamepadSnapshot
+{
+    pub available: bool,
+    pub buttons_down: BTreeSet<String>,
+    pub axes: BTreeMap<String, f32>,
+}
+
+// Everything that was held down, and where, at one point in time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputSnapshot
+{
+    pub keys_down: BTreeSet<String>,
+    pub mouse_position: (f32, f32),
+    pub mouse_buttons_down: BTreeSet<String>,
+    pub mouse_wheel: f32,
+    pub gamepads: Vec<GamepadSnapshot>,
+}
+
+impl InputSnapshot
+{
+    pub fn capture(rl: &RaylibHandle) -> Self
+    {
+        let mouse_position = rl.get_mouse_position();
+
+        InputSnapshot
+        {
+            keys_down: KEYS.iter()
+                .filter(|(_, key)| rl.is_key_down(*key))
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            mouse_position: (mouse_position.x, mouse_position.y),
+            mouse_buttons_down: MOUSE_BUTTONS.iter()
+                .filter(|(_, button)| rl.is_mouse_button_down(*button))
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            mouse_wheel: rl.get_mouse_wheel_move(),
+            gamepads: (0..MAX_GAMEPADS).map(|gamepad|
+            {
+                if !rl.is_gamepad_available(gamepad)
+                {
+                    return GamepadSnapshot::default();
+                }
+
+                GamepadSnapshot
+                {
+                    available: true,
+                    buttons_down: GAMEPAD_BUTTONS.iter()
+                        .filter(|(_, button)| rl.is_gamepad_button_down(gamepad, *button))
+                        .map(|(name, _)| name.to_string())
+                        .collect(),
+                    axes: GAMEPAD_AXES.iter()
+                        .map(|(name, axis)| (name.to_string(), rl.get_gamepad_axis_movement(gamepad, *axis)))
+                        .collect(),
+                }
+            }).collect(),
+        }
+    }
+
+    fn gamepad(&self, gamepad: usize) -> Option<&GamepadSnapshot>
+    {
+        self.gamepads.get(gamepad).filter(|pad| pad.available)
+    }
+}
+
+pub fn is_known_key(name: &str) -> bool
+{
+    KEYS.iter().any(|(key, _)| *key == name)
+}
+
+// The state of the current fixed update and the one before it, for pressed and released checks.
+#[derive(Default)]
+pub struct InputState
+{
+    pub current: InputSnapshot,
+    pub previous: InputSnapshot,
+}
+
+thread_local!
+{
+    static INPUT_STATE: RefCell<InputState> = RefCell::new(InputState::default());
+}
+
+// Moves the input on to a new snapshot. Called once per fixed update.
+pub fn advance(snapshot: InputSnapshot)
+{
+    INPUT_STATE.with(|state|
+    {
+        let mut state = state.borrow_mut();
+        state.previous = std::mem::replace(&mut state.current, snapshot);
+    });
+}
+
+pub fn with_input<R>(f: impl FnOnce(&InputState) -> R) -> R
+{
+    INPUT_STATE.with(|state| f(&state.borrow()))
+}
+
+impl InputState
+{
+    pub fn is_key_down(&self, key: &str) -> bool
+    {
+        self.current.keys_down.contains(key)
+    }
+
+    pub fn is_key_pressed(&self, key: &str) -> bool
+    {
+        self.current.keys_down.contains(key) && !self.previous.keys_down.contains(key)
+    }
+
+    pub fn is_key_released(&self, key: &str) -> bool
+    {
+        !self.current.keys_down.contains(key) && self.previous.keys_down.contains(key)
+    }
+
+    pub fn is_mouse_button_down(&self, button: &str) -> bool
+    {
+        self.current.mouse_buttons_down.contains(button)
+    }
+
+    pub fn is_mouse_button_pressed(&self, button: &str) -> bool
+    {
+        self.current.mouse_buttons_down.contains(button) && !self.previous.mouse_buttons_down.contains(button)
+    }
+
+    pub fn is_mouse_button_released(&self, button: &str) -> bool
+    {
+        !self.current.mouse_buttons_down.contains(button) && self.previous.mouse_buttons_down.contains(button)
+    }
+
+    pub fn is_gamepad_available(&self, gamepad: usize) -> bool
+    {
+        self.current.gamepad(gamepad).is_some()
+    }
+
+    pub fn is_gamepad_button_down(&self, gamepad: usize, button: &str) -> bool
+    {
+        self.current.gamepad(gamepad).map(|pad| pad.buttons_down.contains(button)).unwrap_or(false)
+    }
+
+    pub fn is_gamepad_button_pressed(&self, gamepad: usize, button: &str) -> bool
+    {
+        self.is_gamepad_button_down(gamepad, button)
+            && !self.previous.gamepad(gamepad).map(|pad| pad.buttons_down.contains(button)).unwrap_or(false)
+    }
+
+    pub fn is_gamepad_button_released(&self, gamepad: usize, button: &str) -> bool
+    {
+        !self.is_gamepad_button_down(gamepad, button)
+            && self.previous.gamepad(gamepad).map(|pad| pad.buttons_down.contains(button)).unwrap_or(false)
+    }
+
+    pub fn gamepad_axis(&self, gamepad: usize, axis: &str) -> f32
+    {
+        self.current.gamepad(gamepad).and_then(|pad| pad.axes.get(axis).copied()).unwrap_or(0.0)
+    }
+}
+
+// Something that can trigger an action.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Binding
+{
+    Key(String),
+    MouseButton(String),
+    // Checked on every connected gamepad.
+    GamepadButton(String),
+}
+
+impl Binding
+{
+    pub fn new(kind: &str, name: &str) -> Option<Binding>
+    {
+        match kind
+        {
+            "key" | "keys" if is_known_key(name) => Some(Binding::Key(name.to_string())),
+            "mouse" if MOUSE_BUTTONS.iter().any(|(button, _)| *button == name) => Some(Binding::MouseButton(name.to_string())),
+            "gamepad" if GAMEPAD_BUTTONS.iter().any(|(button, _)| *button == name) => Some(Binding::GamepadButton(name.to_string())),
+            _ => None
+        }
+    }
+
+    fn kind(&self) -> &str
+    {
+        match self
+        {
+            Binding::Key(_) => "keys",
+            Binding::MouseButton(_) => "mouse",
+            Binding::GamepadButton(_) => "gamepad",
+        }
+    }
+
+    fn name(&self) -> &str
+    {
+        match self
+        {
+            Binding::Key(name) | Binding::MouseButton(name) | Binding::GamepadButton(name) => name,
+        }
+    }
+
+    fn is_down(&self, snapshot: &InputSnapshot) -> bool
+    {
+        match self
+        {
+            Binding::Key(key) => snapshot.keys_down.contains(key),
+            Binding::MouseButton(button) => snapshot.mouse_buttons_down.contains(button),
+            Binding::GamepadButton(button) => snapshot.gamepads.iter()
+                .any(|pad| pad.available && pad.buttons_down.contains(button)),
+        }
+    }
+}
+
+// Named actions and the bindings that trigger them. Read from the actions section of the
+// game yaml, which looks like:
+//   jump:
+//     keys: [space, w]
+//     mouse: [left]
+//     gamepad: [a]
+#[derive(Clone, Debug, Default)]
+pub struct ActionMap
+{
+    pub actions: BTreeMap<String, Vec<Binding>>,
+}
+
+impl ActionMap
+{
+    pub fn from_yaml(actions: &Yaml) -> Self
+    {
+        let mut map = ActionMap::default();
+        map.apply_yaml(actions);
+        map
+    }
+
+    // Replaces the bindings of every action in the yaml, leaving other actions as they are.
+    pub fn apply_yaml(&mut self, actions: &Yaml)
+    {
+        if let Yaml::Hash(hash) = actions
+        {
+            for action in hash
+            {
+                let action_name = match action.0.as_str()
+                {
+                    Some(action_name) => action_name,
+                    None => continue,
+                };
+
+                let mut bindings = vec![];
+                if let Yaml::Hash(kinds) = action.1
+                {
+                    for kind in kinds
+                    {
+                        let kind_name = kind.0.as_str().unwrap_or("");
+                        for name in kind.1.as_vec().unwrap_or(&vec![])
+                        {
+                            match name.as_str().and_then(|name| Binding::new(kind_name, name))
+                            {
+                                Some(binding) => bindings.push(binding),
+                                None => log_warn!("input", "Invalid binding {:?} under {} for action {}.", name, kind_name, action_name),
+                            }
+                        }
+                    }
+                }
+
+                self.actions.insert(action_name.to_string(), bindings);
+            }
+        }
+    }
+
+    pub fn to_yaml(&self) -> Yaml
+    {
+        let mut hash = Hash::new();
+        for (action, bindings) in &self.actions
+        {
+            let mut kinds = Hash::new();
+            for binding in bindings
+            {
+                let entry = kinds.entry(Yaml::String(binding.kind().to_string())).or_insert(Yaml::Array(vec![]));
+                if let Yaml::Array(names) = entry
+                {
+                    names.push(Yaml::String(binding.name().to_string()));
+                }
+            }
+            hash.insert(Yaml::String(action.clone()), Yaml::Hash(kinds));
+        }
+
+        Yaml::Hash(hash)
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding)
+    {
+        let bindings = self.actions.entry(action.to_string()).or_insert(vec![]);
+        if !bindings.contains(&binding)
+        {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn clear(&mut self, action: &str)
+    {
+        if let Some(bindings) = self.actions.get_mut(action)
+        {
+            bindings.clear();
+        }
+    }
+
+    // Loads user bindings saved with save, on top of the current ones.
+    pub fn load_overrides(&mut self, path: &str)
+    {
+        if let Ok(file) = fs::read_to_string(path)
+        {
+            match YamlLoader::load_from_str(&file)
+            {
+                Ok(yaml) => if let Some(doc) = yaml.first() { self.apply_yaml(doc); },
+                Err(error) => log_error!("input", "Failed to load input bindings {} due to error: {}.", path, error),
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String>
+    {
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&self.to_yaml()).map_err(|error| format!("{:?}", error))?;
+        fs::write(path, out).map_err(|error| error.to_string())
+    }
+}
+
+impl InputState
+{
+    fn is_action_down_in(snapshot: &InputSnapshot, bindings: Option<&Vec<Binding>>) -> bool
+    {
+        bindings.map(|bindings| bindings.iter().any(|binding| binding.is_down(snapshot))).unwrap_or(false)
+    }
+
+    pub fn is_action_down(&self, actions: &ActionMap, action: &str) -> bool
+    {
+        InputState::is_action_down_in(&self.current, actions.actions.get(action))
+    }
+
+    pub fn is_action_pressed(&self, actions: &ActionMap, action: &str) -> bool
+    {
+        self.is_action_down(actions, action)
+            && !InputState::is_action_down_in(&self.previous, actions.actions.get(action))
+    }
+
+    pub fn is_action_released(&self, actions: &ActionMap, action: &str) -> bool
+    {
+        !self.is_action_down(actions, action)
+            && InputState::is_action_down_in(&self.previous, actions.actions.get(action))
+    }
+}
+
+// The game's action map, plus the user file rebinds are saved to.
+thread_local!
+{
+    static ACTION_MAP: RefCell<ActionMap> = RefCell::new(ActionMap::default());
+    static BINDINGS_PATH: RefCell<String> = RefCell::new(String::new());
+}
+
+pub fn set_action_map(actions: ActionMap, bindings_path: &str)
+{
+    ACTION_MAP.with(|map| *map.borrow_mut() = actions);
+    BINDINGS_PATH.with(|path| *path.borrow_mut() = bindings_path.to_string());
+}
+
+// Forgets the input and the action map, such as when a game shuts down.
+pub fn reset()
+{
+    INPUT_STATE.with(|state| *state.borrow_mut() = InputState::default());
+    ACTION_MAP.with(|map| *map.borrow_mut() = ActionMap::default());
+    BINDINGS_PATH.with(|path| path.borrow_mut().clear());
+}
+
+pub fn with_actions<R>(f: impl FnOnce(&mut ActionMap) -> R) -> R
+{
+    ACTION_MAP.with(|map| f(&mut map.borrow_mut()))
+}
+
+pub fn bindings_path() -> String
+{
+    BINDINGS_PATH.with(|path| path.borrow().clone())
+}
+
+pub fn is_action_down(action: &str) -> bool
+{
+    with_actions(|actions| with_input(|state| state.is_action_down(actions, action)))
+}
+
+pub fn is_action_pressed(action: &str) -> bool
+{
+    with_actions(|actions| with_input(|state| state.is_action_pressed(actions, action)))
+}
+
+pub fn is_action_released(action: &str) -> bool
+{
+    with_actions(|actions| with_input(|state| state.is_action_released(actions, action)))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::temp_directory;
+
+    fn keys(keys: &[&str]) -> InputSnapshot
+    {
+        let mut snapshot = InputSnapshot::default();
+        snapshot.keys_down = keys.iter().map(|key| key.to_string()).collect();
+        snapshot
+    }
+
+    #[test]
+    fn keys_are_pressed_and_released_across_advance()
+    {
+        advance(keys(&["space"]));
+        with_input(|state|
+        {
+            assert!(state.is_key_down("space"));
+            assert!(state.is_key_pressed("space"));
+            assert!(!state.is_key_released("space"));
+        });
+
+        advance(keys(&["space"]));
+        with_input(|state|
+        {
+            assert!(state.is_key_down("space"));
+            assert!(!state.is_key_pressed("space"));
+        });
+
+        advance(keys(&[]));
+        with_input(|state|
+        {
+            assert!(!state.is_key_down("space"));
+            assert!(state.is_key_released("space"));
+        });
+
+        advance(keys(&[]));
+        with_input(|state| assert!(!state.is_key_released("space")));
+    }
+
+    #[test]
+    fn gamepad_buttons_need_a_connected_gamepad()
+    {
+        let mut pad = GamepadSnapshot::default();
+        pad.buttons_down.insert("a".to_string());
+        let mut snapshot = InputSnapshot::default();
+        snapshot.gamepads.push(pad);
+
+        advance(snapshot.clone());
+        with_input(|state| assert!(!state.is_gamepad_button_down(0, "a")));
+
+        snapshot.gamepads[0].available = true;
+        advance(snapshot);
+        with_input(|state|
+        {
+            assert!(state.is_gamepad_button_down(0, "a"));
+            assert!(state.is_gamepad_button_pressed(0, "a"));
+            assert!(!state.is_gamepad_available(1));
+        });
+    }
+
+    fn actions(source: &str) -> ActionMap
+    {
+        ActionMap::from_yaml(&YamlLoader::load_from_str(source).unwrap()[0])
+    }
+
+    #[test]
+    fn reads_actions_and_skips_invalid_bindings()
+    {
+        let map = actions("jump:\n  keys: [space, w, not_a_key]\n  mouse: [left]\n  gamepad: [a]");
+        assert_eq!(map.actions["jump"], vec![
+            Binding::Key("space".to_string()),
+            Binding::Key("w".to_string()),
+            Binding::MouseButton("left".to_string()),
+            Binding::GamepadButton("a".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn rebinding_replaces_only_the_given_actions()
+    {
+        let mut map = actions("jump:\n  keys: [space]\nfire:\n  mouse: [left]");
+
+        map.clear("jump");
+        map.bind("jump", Binding::Key("w".to_string()));
+        map.bind("jump", Binding::Key("w".to_string()));
+        assert_eq!(map.actions["jump"], vec![Binding::Key("w".to_string())]);
+
+        map.apply_yaml(&YamlLoader::load_from_str("fire:\n  keys: [f]").unwrap()[0]);
+        assert_eq!(map.actions["fire"], vec![Binding::Key("f".to_string())]);
+        assert_eq!(map.actions["jump"], vec![Binding::Key("w".to_string())]);
+    }
+
+    #[test]
+    fn saved_bindings_load_over_the_defaults()
+    {
+        let directory = temp_directory("input_bindings");
+        let path = directory.join("bindings.yaml");
+        let path = path.to_str().unwrap();
+
+        let mut rebound = actions("jump:\n  keys: [space]");
+        rebound.clear("jump");
+        rebound.bind("jump", Binding::GamepadButton("b".to_string()));
+        rebound.bind("pause", Binding::Key("escape".to_string()));
+        rebound.save(path).unwrap();
+
+        let mut loaded = actions("jump:\n  keys: [space]\nfire:\n  mouse: [left]");
+        loaded.load_overrides(path);
+        let _ = fs::remove_dir_all(&directory);
+
+        assert_eq!(loaded.actions["jump"], vec![Binding::GamepadButton("b".to_string())]);
+        assert_eq!(loaded.actions["pause"], vec![Binding::Key("escape".to_string())]);
+        assert_eq!(loaded.actions["fire"], vec![Binding::MouseButton("left".to_string())]);
+    }
+
+    #[test]
+    fn actions_are_pressed_and_released_across_advance()
+    {
+        set_action_map(actions("jump:\n  keys: [space, w]"), "");
+
+        advance(keys(&["space"]));
+        assert!(is_action_down("jump"));
+        assert!(is_action_pressed("jump"));
+
+        // Swapping to another of its bindings keeps the action held.
+        advance(keys(&["w"]));
+        assert!(is_action_down("jump"));
+        assert!(!is_action_pressed("jump"));
+        assert!(!is_action_released("jump"));
+
+        advance(keys(&[]));
+        assert!(is_action_released("jump"));
+        assert!(!is_action_down("unknown"));
+
+        reset();
+    }
+}
@@ -0,0 +1,192 @@
+use crate::input::{InputSnapshot, GamepadSnapshot};
+use crate::scene_manager::read_f32;
+use crate::scene_manager::object_registry::write_f32;
+use std::collections::VecDeque;
+use std::fs;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+use yaml_rust::yaml::Hash;
+
+const RECORDING_VERSION: i64 = 2;
+
+// How many fixed updates a frame ran and the delta its process call was given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordedFrame
+{
+    pub ticks: u32,
+    pub delta: f32,
+}
+
+// Input given to every fixed update, along with what is needed to play it back the same way.
+pub struct InputRecording
+{
+    pub seed: u64,
+    pub fixed_update_rate: f32,
+    pub ticks: VecDeque<InputSnapshot>,
+    pub frames: VecDeque<RecordedFrame>,
+}
+
+impl InputRecording
+{
+    pub fn new(seed: u64, fixed_update_rate: f32) -> Self
+    {
+        InputRecording
+        {
+            seed,
+            fixed_update_rate,
+            ticks: VecDeque::new(),
+            frames: VecDeque::new(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String>
+    {
+        let mut doc = Hash::new();
+        doc.insert(Yaml::String("version".to_string()), Yaml::Integer(RECORDING_VERSION));
+        // Stored as a string, yaml integers are signed.
+        doc.insert(Yaml::String("seed".to_string()), Yaml::String(self.seed.to_string()));
+        doc.insert(Yaml::String("fixed_update_rate".to_string()), write_f32(self.fixed_update_rate));
+        doc.insert(Yaml::String("ticks".to_string()), Yaml::Array(self.ticks.iter().map(snapshot_to_yaml).collect()));
+        doc.insert(Yaml::String("frames".to_string()), Yaml::Array(self.frames.iter()
+            .map(|frame| Yaml::Array(vec![Yaml::Integer(frame.ticks as i64), write_f32(frame.delta)]))
+            .collect()));
+
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&Yaml::Hash(doc)).map_err(|error| format!("{:?}", error))?;
+        fs::write(path, out).map_err(|error| error.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String>
+    {
+        let file = fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let yaml = YamlLoader::load_from_str(&file).map_err(|error| error.to_string())?;
+        let doc = yaml.first().ok_or("The recording is empty.")?;
+
+        if doc["version"].as_i64() != Some(RECORDING_VERSION)
+        {
+            return Err(format!("Unsupported recording version {:?}.", doc["version"]));
+        }
+
+        Ok(InputRecording
+        {
+            seed: doc["seed"].as_str().and_then(|seed| seed.parse().ok()).ok_or("The recording has no seed.")?,
+            fixed_update_rate: read_f32(&doc["fixed_update_rate"]).ok_or("The recording has no fixed_update_rate.")?,
+            ticks: doc["ticks"].as_vec().map(|ticks| ticks.iter().map(yaml_to_snapshot).collect()).unwrap_or_default(),
+            frames: doc["frames"].as_vec().map(|frames| frames.iter().map(|frame| RecordedFrame
+            {
+                ticks: frame[0].as_i64().unwrap_or(0).max(0) as u32,
+                delta: read_f32(&frame[1]).unwrap_or(0.0),
+            }).collect()).unwrap_or_default(),
+        })
+    }
+}
+
+fn names(names: &Yaml) -> Vec<String>
+{
+    names.as_vec().map(|names| names.iter().filter_map(|name| name.as_str().map(|name| name.to_string())).collect())
+        .unwrap_or_default()
+}
+
+fn snapshot_to_yaml(snapshot: &InputSnapshot) -> Yaml
+{
+    let mut tick = Hash::new();
+    tick.insert(Yaml::String("keys".to_string()),
+        Yaml::Array(snapshot.keys_down.iter().map(|key| Yaml::String(key.clone())).collect()));
+    tick.insert(Yaml::String("mouse".to_string()),
+        Yaml::Array(vec![write_f32(snapshot.mouse_position.0), write_f32(snapshot.mouse_position.1)]));
+    tick.insert(Yaml::String("buttons".to_string()),
+        Yaml::Array(snapshot.mouse_buttons_down.iter().map(|button| Yaml::String(button.clone())).collect()));
+    tick.insert(Yaml::String("wheel".to_string()), write_f32(snapshot.mouse_wheel));
+
+    let gamepads = snapshot.gamepads.iter().map(|pad|
+    {
+        let mut gamepad = Hash::new();
+        gamepad.insert(Yaml::String("available".to_string()), Yaml::Boolean(pad.available));
+        gamepad.insert(Yaml::String("buttons".to_string()),
+            Yaml::Array(pad.buttons_down.iter().map(|button| Yaml::String(button.clone())).collect()));
+
+        let mut axes = Hash::new();
+        for (axis, value) in &pad.axes
+        {
+            axes.insert(Yaml::String(axis.clone()), write_f32(*value));
+        }
+        gamepad.insert(Yaml::String("axes".to_string()), Yaml::Hash(axes));
+
+        Yaml::Hash(gamepad)
+    }).collect();
+    tick.insert(Yaml::String("gamepads".to_string()), Yaml::Array(gamepads));
+
+    Yaml::Hash(tick)
+}
+
+fn yaml_to_snapshot(tick: &Yaml) -> InputSnapshot
+{
+    InputSnapshot
+    {
+        keys_down: names(&tick["keys"]).into_iter().collect(),
+        mouse_position: (read_f32(&tick["mouse"][0]).unwrap_or(0.0), read_f32(&tick["mouse"][1]).unwrap_or(0.0)),
+        mouse_buttons_down: names(&tick["buttons"]).into_iter().collect(),
+        mouse_wheel: read_f32(&tick["wheel"]).unwrap_or(0.0),
+        gamepads: tick["gamepads"].as_vec().map(|pads| pads.iter().map(|pad| GamepadSnapshot
+        {
+            available: pad["available"].as_bool().unwrap_or(false),
+            buttons_down: names(&pad["buttons"]).into_iter().collect(),
+            axes: pad["axes"].as_hash().map(|axes| axes.iter()
+                .filter_map(|(axis, value)| Some((axis.as_str()?.to_string(), read_f32(value)?)))
+                .collect()).unwrap_or_default(),
+        }).collect()).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::temp_directory;
+
+    #[test]
+    fn save_and_load_round_trip()
+    {
+        let mut recording = InputRecording::new(u64::MAX, 60.0);
+
+        let mut snapshot = InputSnapshot::default();
+        snapshot.keys_down.insert("space".to_string());
+        snapshot.mouse_position = (12.5, -3.0);
+        snapshot.mouse_buttons_down.insert("left".to_string());
+        snapshot.mouse_wheel = 1.0;
+        let mut gamepad = GamepadSnapshot::default();
+        gamepad.available = true;
+        gamepad.buttons_down.insert("a".to_string());
+        gamepad.axes.insert("left_x".to_string(), 0.1);
+        snapshot.gamepads.push(gamepad);
+
+        recording.ticks.push_back(snapshot);
+        recording.ticks.push_back(InputSnapshot::default());
+        recording.frames.push_back(RecordedFrame { ticks: 2, delta: 0.016666668 });
+        recording.frames.push_back(RecordedFrame { ticks: 0, delta: 0.1 });
+
+        let directory = temp_directory("recording");
+        let path = directory.join("recording.yaml");
+        let path = path.to_str().unwrap();
+        recording.save(path).unwrap();
+        let loaded = InputRecording::load(path).unwrap();
+        let _ = fs::remove_dir_all(&directory);
+
+        assert_eq!(loaded.seed, recording.seed);
+        assert_eq!(loaded.fixed_update_rate, recording.fixed_update_rate);
+        assert_eq!(loaded.ticks, recording.ticks);
+        assert_eq!(loaded.frames, recording.frames);
+    }
+
+    #[test]
+    fn rejects_other_versions()
+    {
+        let directory = temp_directory("old_recording");
+        let path = directory.join("recording.yaml");
+        let path = path.to_str().unwrap();
+        fs::write(path, "version: 1\nseed: \"1\"\nfixed_update_rate: 30\nticks: []").unwrap();
+        let loaded = InputRecording::load(path);
+        let _ = fs::remove_dir_all(&directory);
+
+        assert!(loaded.is_err());
+    }
+}
@@ -8,22 +8,40 @@ extern crate downcast_rs;
 pub mod scene_manager;
 pub mod object;
 pub mod transform;
+pub mod game_config;
+pub mod time;
+pub mod input;
+pub mod input_recording;
+pub mod random;
+pub mod save_data;
+pub mod script_error;
+pub mod logging;
 
 mod drython_extensions;
+#[cfg(test)]
+mod test_support;
 
-use crate::object::Object2D;
+use crate::object::{Object2D, Object3D, TObject};
+use crate::{log_error, log_info, log_warn};
 use drython::types::Token;
 use raylib::RaylibHandle;
 use raylib::RaylibThread;
 use std::fs;
 use std::time::Duration;
+use scene_manager::scene::Scene;
 use std::time::SystemTime;
 
 use raylib::prelude::*;
 
 extern crate yaml_rust;
 
-use scene_manager::SceneManager;
+use game_config::GameConfig;
+use input::{InputSnapshot, ActionMap};
+use input_recording::{InputRecording, RecordedFrame};
+use script_error::ErrorSink;
+use logging::Level;
+use scene_manager::{SceneManager, SceneRequest};
+use scene_manager::object_registry::{ObjectLoader, ObjectSaver};
 use yaml_rust::{YamlLoader, Yaml};
 
 pub struct Game
@@ -31,9 +49,35 @@ pub struct Game
     name: String,
     main_scene_path: String,
     scene_manager: SceneManager,
+
+    pub config: GameConfig,
+    raylib: Option<(RaylibHandle, RaylibThread)>,
+    accumulator: Duration,
+
+    // Latest input read from the window, and mouse wheel movement not yet given to an update.
+    input_snapshot: InputSnapshot,
+    pending_mouse_wheel: f32,
+
+    record_path: String,
+    recording: Option<InputRecording>,
+    replay: Option<InputRecording>,
+
+    // Real time since changed assets were last looked for.
+    hot_reload_timer: Duration,
+    // Time since the recording was last saved.
+    recording_flush_timer: Duration,
 }
 
-type Raylib<'a> = (&'a mut RaylibHandle, &'a RaylibThread);
+const RECORDING_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+pub type Raylib<'a> = (&'a mut RaylibHandle, &'a RaylibThread);
+
+// How long a headless run should simulate for.
+pub enum RunLength
+{
+    Ticks(u32),
+    Duration(Duration),
+}
 
 impl Game
 {
@@ -45,109 +89,732 @@ impl Game
             name: String::new(),
             main_scene_path: main_yaml_path.to_string(),
             scene_manager: SceneManager::new(),
+
+            config: GameConfig::new(),
+            raylib: None,
+            accumulator: Duration::new(0, 0),
+
+            input_snapshot: InputSnapshot::default(),
+            pending_mouse_wheel: 0.0,
+
+            record_path: String::new(),
+            recording: None,
+            replay: None,
+
+            hot_reload_timer: Duration::new(0, 0),
+            recording_flush_timer: Duration::new(0, 0),
         }
     }
 
+    // Opens a window and runs the game until it is closed.
     pub fn start(&mut self) -> &mut Self
     {
-        let (mut rl, thread) = raylib::init()
-            .size(640, 480)
-            .title(&self.name)
-            .build();
+        self.init();
 
-        match fs::read_to_string(&self.main_scene_path)
+        // Game Loop
+        let mut last_update_time = SystemTime::now();
+
+        while !self.should_close()
         {
-            Ok(file) =>
+            let now = SystemTime::now();
+            let real_delta_time = now.duration_since(last_update_time).unwrap_or(Duration::new(0, 0));
+            last_update_time += real_delta_time;
+
+            self.step(real_delta_time);
+            self.render();
+        }
+
+        self.shutdown();
+
+        self
+    }
+
+    // Creates the window, loads the startup yaml and starts the main scene.
+    // Frames are then driven by the host through step and render.
+    pub fn init(&mut self) -> &mut Self
+    {
+        let startup = self.read_startup();
+        if let Some(contents) = &startup
+        {
+            self.apply_config(&contents[0]);
+        }
+
+        let mut builder = raylib::init();
+        builder
+            .size(self.config.window_width, self.config.window_height)
+            .title(&self.name);
+        if self.config.fullscreen { builder.fullscreen(); }
+        if self.config.resizable { builder.resizable(); }
+        if self.config.vsync { builder.vsync(); }
+
+        let (mut rl, thread) = builder.build();
+        if self.config.target_fps > 0
+        {
+            rl.set_target_fps(self.config.target_fps);
+        }
+
+        if let Some(contents) = startup
+        {
+            self.startup_yaml(Some(&mut (&mut rl, &thread)), contents);
+        }
+        self.raylib = Some((rl, thread));
+
+        self.start_scene();
+
+        self
+    }
+
+    // Same as init, but without a window or any textures.
+    pub fn init_headless(&mut self) -> &mut Self
+    {
+        if let Some(contents) = self.read_startup()
+        {
+            self.apply_config(&contents[0]);
+            self.startup_yaml(None, contents);
+        }
+        self.start_scene();
+
+        self
+    }
+
+    // Lets scenes create objects of a custom type through the type key of their objects.
+    pub fn register_object_type(&mut self, type_name: &str, loader: ObjectLoader, saver: ObjectSaver) -> &mut Self
+    {
+        self.scene_manager.object_types.register(type_name, loader, saver);
+        self
+    }
+
+    // Saves the scene on top of the stack.
+    pub fn save_scene(&self, path: &str) -> Result<(), String>
+    {
+        match self.scene_manager.current_scene()
+        {
+            Some(scene) => scene.save(path),
+            None => Err("There is no scene loaded to save.".to_string()),
+        }
+    }
+
+    // Records the input of every fixed update, saved to the path every few seconds and on shutdown.
+    // Must be called before init, takes priority over the game yaml.
+    pub fn record_input(&mut self, path: &str) -> &mut Self
+    {
+        self.config.record_input = path.to_string();
+        self
+    }
+
+    // Plays back a recording instead of live input. Must be called before init,
+    // takes priority over the game yaml.
+    pub fn replay_input(&mut self, path: &str) -> &mut Self
+    {
+        self.config.replay_input = path.to_string();
+        self
+    }
+
+    // Scales the time given to updates. 0 pauses the game.
+    pub fn set_time_scale(&mut self, scale: f32) -> Result<(), String>
+    {
+        time::set_time_scale(scale)
+    }
+
+    pub fn time_scale(&self) -> f32
+    {
+        time::time_scale()
+    }
+
+    pub fn should_close(&self) -> bool
+    {
+        if script_error::halt_requested()
+        {
+            return true;
+        }
+
+        match &self.raylib
+        {
+            Some((rl, _)) => rl.window_should_close(),
+            None => false,
+        }
+    }
+
+    // Replaces where script errors are sent, printing them by default.
+    pub fn set_error_sink(&mut self, sink: ErrorSink) -> &mut Self
+    {
+        script_error::set_error_sink(sink);
+        self
+    }
+
+    // Advances the game by the given real time. Runs as many fixed updates as fit,
+    // each given the fixed step, then a single process call with the frame delta.
+    // While replaying, the recorded number of fixed updates and delta are used instead.
+    pub fn step(&mut self, dt: Duration)
+    {
+        if let Some((rl, _)) = &self.raylib
+        {
+            self.input_snapshot = InputSnapshot::capture(rl);
+            self.pending_mouse_wheel += self.input_snapshot.mouse_wheel;
+        }
+
+        let target_frame_time = self.config.fixed_step();
+        match self.next_replay_frame()
+        {
+            Some(frame) => self.run_frame(frame.ticks, frame.delta),
+            None =>
             {
-                match YamlLoader::load_from_str(file.as_str())
+                let scaled_dt = dt.mul_f32(time::time_scale());
+                self.accumulator += scaled_dt;
+
+                // Handle frame rate based UPDATING.
+                let mut ticks = 0;
+                while self.accumulator >= target_frame_time
                 {
-                    Ok(yaml) =>
+                    if ticks >= self.config.max_catch_up_ticks
                     {
-                        self.startup_yaml(&mut (&mut rl, &thread), yaml);
+                        // Too far behind, drop the backlog instead of trying to catch up forever.
+                        self.accumulator = Duration::from_nanos(
+                            (self.accumulator.as_nanos() % target_frame_time.as_nanos()) as u64);
+                        break;
                     }
-                    Err(error) =>
+
+                    self.accumulator -= target_frame_time;
+                    ticks += 1;
+                }
+
+                self.run_frame(ticks, scaled_dt.as_secs_f32());
+            }
+        }
+
+        if self.config.hot_reload
+        {
+            // Real time, so assets still reload while the game is paused.
+            self.hot_reload_timer += dt;
+            if self.hot_reload_timer.as_secs_f32() >= self.config.hot_reload_interval
+            {
+                self.hot_reload_timer = Duration::new(0, 0);
+                self.hot_reload();
+            }
+        }
+
+        self.flush_recording(dt);
+    }
+
+    // Runs the fixed updates and the process call of one frame, applying the scene
+    // changes scripts asked for after each of them so they happen on the same update
+    // whichever frame it falls in.
+    fn run_frame(&mut self, ticks: u32, delta: f32)
+    {
+        let fixed_delta = self.config.fixed_step().as_secs_f32();
+        for _ in 0..ticks
+        {
+            self.tick(fixed_delta);
+            self.apply_scene_requests();
+        }
+
+        self.process(delta);
+        self.apply_scene_requests();
+
+        if let Some(recording) = &mut self.recording
+        {
+            recording.frames.push_back(RecordedFrame { ticks, delta });
+        }
+    }
+
+    // The next frame of the replay, if one is playing and has frames left.
+    fn next_replay_frame(&mut self) -> Option<RecordedFrame>
+    {
+        let frame = self.replay.as_mut()?.frames.pop_front();
+        if frame.is_none()
+        {
+            self.finish_replay();
+        }
+        frame
+    }
+
+    fn finish_replay(&mut self)
+    {
+        log_info!("game", "Input replay finished, switching to live input.");
+        self.replay = None;
+    }
+
+    // Changes the scene stack the way scripts asked during the last update.
+    fn apply_scene_requests(&mut self)
+    {
+        for request in scene_manager::take_scene_requests()
+        {
+            match request
+            {
+                SceneRequest::Change(scene_path) => self.change_scene(&scene_path),
+                SceneRequest::Push(scene_path) => self.push_scene(&scene_path),
+                SceneRequest::Overlay(scene_path) => self.overlay_scene(&scene_path),
+                SceneRequest::Pop => self.pop_scene(),
+            }
+        }
+    }
+
+    // Unloads every scene and starts the given one, relative to the assets folder.
+    pub fn change_scene(&mut self, scene_path: &str)
+    {
+        let mut raylib = self.raylib.as_mut().map(|(rl, thread)| (rl, &*thread));
+        self.scene_manager.load(raylib.as_mut(), &format!("assets/{}", scene_path));
+
+        self.start_scene();
+    }
+
+    // Starts the given scene on top of the others, covering them.
+    pub fn push_scene(&mut self, scene_path: &str)
+    {
+        let mut raylib = self.raylib.as_mut().map(|(rl, thread)| (rl, &*thread));
+        self.scene_manager.push(raylib.as_mut(), &format!("assets/{}", scene_path));
+
+        self.start_scene();
+    }
+
+    // Starts the given scene on top of the others without covering them.
+    pub fn overlay_scene(&mut self, scene_path: &str)
+    {
+        let mut raylib = self.raylib.as_mut().map(|(rl, thread)| (rl, &*thread));
+        self.scene_manager.overlay(raylib.as_mut(), &format!("assets/{}", scene_path));
+
+        self.start_scene();
+    }
+
+    pub fn pop_scene(&mut self)
+    {
+        self.scene_manager.pop();
+    }
+
+    // How far between the last and the next fixed update the current frame is, from 0 to 1.
+    pub fn interpolation_alpha(&self) -> f32
+    {
+        (self.accumulator.as_secs_f32() / self.config.fixed_step().as_secs_f32()).clamp(0.0, 1.0)
+    }
+
+    // Draws the current scene. Does nothing when running headless.
+    pub fn render(&mut self)
+    {
+        let alpha = self.interpolation_alpha();
+
+        if let Some((rl, thread)) = &mut self.raylib
+        {
+            let mut d = rl.begin_drawing(thread);
+
+            d.clear_background(Color::WHITE);
+
+            // 3d object drawing, for scenes with an active 3d camera. Drawn under the 2d objects.
+            for scene in self.scene_manager.drawing_scenes()
+            {
+                if let Some(camera) = scene.active_camera_3d()
+                {
+                    let mut d3 = d.begin_mode3D(camera.to_raylib());
+                    for object in &scene.objects
                     {
-                        println!("Failed to load yaml file: {} due to error: {}.", self.main_scene_path, error);
+                        if let Some(object3d) = object.downcast_ref::<Object3D>()
+                        {
+                            if let Some(world) = scene.world_transform_3d(object3d.get_id())
+                            {
+                                object3d.draw(&mut d3, &world);
+                            }
+                        }
                     }
                 }
             }
-            Err(error) =>
+
+            // 2d object drawing, through the scene's active camera if it has one.
+            for (scene, updating) in self.scene_manager.drawing_scenes_updating()
             {
-                println!("Failed to find startup file: {} due to: {}.", self.main_scene_path, error);
+                let alpha = if updating { alpha } else { 1.0 };
+                match scene.active_camera_2d()
+                {
+                    Some(camera) =>
+                    {
+                        let view = camera.interpolated_view(alpha);
+                        let mut d2 = d.begin_mode2D(Camera2D
+                        {
+                            offset: view.offset,
+                            target: view.target,
+                            rotation: view.rotation,
+                            zoom: view.zoom,
+                        });
+                        Game::draw_scene_2d(&mut d2, scene, alpha);
+                    }
+                    None => Game::draw_scene_2d(&mut d, scene, alpha),
+                }
+            }
+
+            // Latest log messages, over everything else.
+            logging::for_each_overlay_line(|index, level, line|
+            {
+                let color = match level
+                {
+                    Level::Error => Color::RED,
+                    Level::Warn => Color::ORANGE,
+                    _ => Color::DARKGRAY,
+                };
+                d.draw_text(line, 10, 10 + index as i32 * 20, 16, color);
+            });
+        }
+    }
+
+    fn draw_scene_2d(d: &mut impl RaylibDraw, scene: &Scene, alpha: f32)
+    {
+        for object2d in scene.draw_order_2d()
+        {
+            if let Some(world) = scene.world_transform_2d(object2d.get_id(), alpha)
+            {
+                object2d.draw(d, &world);
+            }
+        }
+    }
+
+    // Unloads the current scene and closes the window.
+    pub fn shutdown(&mut self)
+    {
+        self.scene_manager.unload_all();
+
+        self.save_recording();
+        self.recording = None;
+        self.replay = None;
+        Game::reset_engine_state();
+
+        self.accumulator = Duration::new(0, 0);
+        self.raylib = None;
+    }
+
+    // Clears the state shared with scripts outside of the Game, including requests the exit
+    // callbacks made, so another game started on this thread begins from nothing.
+    fn reset_engine_state()
+    {
+        scene_manager::take_scene_requests();
+        scene_manager::take_spawn_requests();
+        scene_manager::take_destroy_requests();
+        object::take_object_requests();
+        object::clear_directory();
+        object::set_active_view(None);
+        object::take_shake_requests();
+        input::reset();
+        let _ = time::set_time_scale(1.0);
+        script_error::clear_halt();
+    }
+
+    // Runs the startup yaml and its scripts without opening a window.
+    // Runs exactly the given number of fixed updates, each followed by a process call
+    // given the fixed step whatever the time scale, so runs are repeatable.
+    // Returns the final scene so its objects can be inspected.
+    pub fn start_headless(&mut self, length: RunLength) -> Option<Scene>
+    {
+        self.init_headless();
+
+        let fixed_step = self.config.fixed_step();
+        let ticks = match length
+        {
+            RunLength::Ticks(ticks) => ticks,
+            RunLength::Duration(duration) => (duration.as_secs_f64() / fixed_step.as_secs_f64()) as u32,
+        };
+
+        // A replay keeps the way its fixed updates were spread over frames.
+        let mut remaining = ticks;
+        while remaining > 0 && !self.should_close()
+        {
+            let (frame_ticks, delta) = match self.next_replay_frame()
+            {
+                Some(frame) => (frame.ticks.min(remaining), frame.delta),
+                None => (1, fixed_step.as_secs_f32()),
+            };
+
+            self.run_frame(frame_ticks, delta);
+            self.flush_recording(fixed_step.mul_f32(frame_ticks as f32));
+            remaining -= frame_ticks;
+        }
+
+        self.scene_manager.scenes.pop()
+    }
+
+    fn tick(&mut self, fixed_delta: f32)
+    {
+        let mut snapshot = self.input_snapshot.clone();
+        snapshot.mouse_wheel = self.pending_mouse_wheel;
+        self.pending_mouse_wheel = 0.0;
+
+        if let Some(replay) = &mut self.replay
+        {
+            match replay.ticks.pop_front()
+            {
+                Some(recorded) => snapshot = recorded,
+                None => self.finish_replay(),
             }
         }
 
-        if let Some(current_scene) = &mut self.scene_manager.current_scene
+        if let Some(recording) = &mut self.recording
+        {
+            recording.ticks.push_back(snapshot.clone());
+        }
+
+        input::advance(snapshot);
+
+        self.publish_objects();
+
+        for scene in self.scene_manager.updating_scenes()
         {
-            current_scene.script_manager.run_setup();
-            current_scene.script_manager.run_function_all("start", None);
-            current_scene.script_manager.update_variables(&mut current_scene.objects);
+            scene.dispatch_input_actions();
 
-            for object in &current_scene.objects
+            for object in &mut scene.objects
             {
-                if let Some(object2d) = object.downcast_ref::<Object2D>()
+                if let Some(object2d) = object.downcast_mut::<Object2D>()
                 {
-                    println!("After Change: {:?}", object2d.object.name);
+                    object2d.reset_interpolation();
                 }
             }
+
+            scene.script_manager.run_function_all(&mut scene.objects, "fixed_update", Some(vec![Token::Float(fixed_delta)]));
+            scene.script_manager.run_function_all(&mut scene.objects, "update", Some(vec![Token::Float(fixed_delta)]));
+            scene.script_manager.update_variables(&mut scene.objects);
         }
 
-        // Game Loop
-        let mut last_update_time = SystemTime::now();
-        let game_time_factor: f32 = 1.0;
-        // 33.3 milliseconds.
-        let target_frame_time = Duration::from_nanos(33_300_000);
-        let mut accumulator = Duration::new(0, 0);
+        let shakes = object::take_shake_requests();
+        for scene in self.scene_manager.updating_scenes()
+        {
+            scene.update_cameras(fixed_delta, &shakes);
+        }
+
+        self.apply_object_requests();
+    }
+
+    fn process(&mut self, delta: f32)
+    {
+        self.publish_objects();
 
-        while !rl.window_should_close()
+        for scene in self.scene_manager.updating_scenes()
         {
-            let now = SystemTime::now();
-            let real_delta_time = now.duration_since(last_update_time).unwrap_or(Duration::new(0, 0));
-            last_update_time += real_delta_time;
-            accumulator += real_delta_time;
-            let game_delta_time = real_delta_time.as_secs_f32() * game_time_factor;
+            scene.script_manager.run_function_all(&mut scene.objects, "process", Some(vec![Token::Float(delta)]));
+            scene.script_manager.update_variables(&mut scene.objects);
+        }
+
+        self.apply_object_requests();
+    }
 
-            // Handle frame rate based UPDATING.
-            while accumulator > target_frame_time
+    // A replay brings its own seed and update rate so it plays out the same way it was recorded.
+    fn setup_recording(&mut self)
+    {
+        self.replay = None;
+        if !self.config.replay_input.is_empty()
+        {
+            match InputRecording::load(&self.config.replay_input)
             {
-                if let Some(current_scene) = &mut self.scene_manager.current_scene
+                Ok(replay) =>
                 {
-                    current_scene.script_manager.run_function_all("update", Some(vec![Token::Float(game_delta_time)]));
-                    current_scene.script_manager.update_variables(&mut current_scene.objects);
+                    self.config.fixed_update_rate = replay.fixed_update_rate;
+                    self.replay = Some(replay);
                 }
-                accumulator -= target_frame_time;
+                Err(error) => log_error!("game", "Failed to load input replay {} due to: {}", self.config.replay_input, error),
             }
+        }
+
+        let seed = match &self.replay
+        {
+            Some(replay) => replay.seed,
+            None => self.config.seed.unwrap_or_else(random::seed_from_time),
+        };
+        random::set_seed(seed);
 
-            let mut d = rl.begin_drawing(&thread);
+        self.record_path = self.config.record_input.clone();
+        self.recording_flush_timer = Duration::new(0, 0);
+        self.recording = if self.record_path.is_empty() { None }
+            else { Some(InputRecording::new(seed, self.config.fixed_update_rate)) };
+    }
 
-            d.clear_background(Color::WHITE);
+    // Saves the recording every few seconds of play, so a crash only loses the last few.
+    fn flush_recording(&mut self, elapsed: Duration)
+    {
+        if self.recording.is_none()
+        {
+            return;
+        }
+
+        self.recording_flush_timer += elapsed;
+        if self.recording_flush_timer >= RECORDING_FLUSH_INTERVAL
+        {
+            self.recording_flush_timer = Duration::new(0, 0);
+            self.save_recording();
+        }
+    }
+
+    // Saves the input recorded so far, if recording.
+    pub fn save_recording(&self)
+    {
+        if let Some(recording) = &self.recording
+        {
+            if let Err(error) = recording.save(&self.record_path)
+            {
+                log_error!("game", "Failed to save input recording {} due to: {}", self.record_path, error);
+            }
+        }
+    }
 
-            // 2d object drawing.
-            if let Some(current_scene) = &self.scene_manager.current_scene
+    fn read_startup(&self) -> Option<Vec<Yaml>>
+    {
+        match fs::read_to_string(&self.main_scene_path)
+        {
+            Ok(file) =>
             {
-                for object in &current_scene.objects
+                match YamlLoader::load_from_str(file.as_str())
                 {
-                    if let Some(object2d) = object.downcast_ref::<Object2D>()
+                    Ok(yaml) =>
                     {
-                        if let Some(texture) = &object2d.sprite
+                        if yaml.is_empty()
                         {
-                            let pos = object2d.transform.pos;
-                            d.draw_texture(texture, pos.x as i32, pos.y as i32, raylib::color::Color::WHITE); 
+                            log_error!("game", "Failed to load yaml file: {} as it is empty.", self.main_scene_path);
+                            return None;
                         }
+                        return Some(yaml);
+                    }
+                    Err(error) =>
+                    {
+                        log_error!("game", "Failed to load yaml file: {} due to error: {}.", self.main_scene_path, error);
                     }
                 }
             }
+            Err(error) =>
+            {
+                log_error!("game", "Failed to find startup file: {} due to: {}.", self.main_scene_path, error);
+            }
         }
 
-        self
+        None
+    }
+
+    fn apply_config(&mut self, contents: &Yaml)
+    {
+        let record_input = self.config.record_input.clone();
+        let replay_input = self.config.replay_input.clone();
+
+        self.config = GameConfig::from_yaml(contents);
+        logging::configure(self.config.logging.clone());
+        if let Err(error) = time::set_time_scale(self.config.time_scale)
+        {
+            log_warn!("config", "{}", error);
+        }
+        script_error::set_error_policy(self.config.script_errors);
+        script_error::clear_halt();
+
+        if !record_input.is_empty() { self.config.record_input = record_input; }
+        if !replay_input.is_empty() { self.config.replay_input = replay_input; }
+        self.setup_recording();
+
+        // User rebinds are saved to and loaded from the input_bindings file.
+        let mut actions = ActionMap::from_yaml(&contents["actions"]);
+        let bindings_path = contents["input_bindings"].as_str().unwrap_or("");
+        if !bindings_path.is_empty()
+        {
+            actions.load_overrides(bindings_path);
+        }
+        input::set_action_map(actions, bindings_path);
+
+        if let Yaml::String(name) = &contents["name"]
+        {
+            self.name = name.to_string();
+        }
+
+        // Saves go in the user's data directory, under the game's name unless one is given.
+        let save_directory = contents["save_directory"].as_str().unwrap_or(&self.name);
+        save_data::set_directory(if save_directory.is_empty() { "drython_game" } else { save_directory });
+    }
+
+    // Reloads every asset file that changed since it was loaded.
+    fn hot_reload(&mut self)
+    {
+        let mut raylib = self.raylib.as_mut().map(|(rl, thread)| (rl, &*thread));
+        self.scene_manager.reload_changed_assets(raylib.as_mut());
+
+        for scene in &mut self.scene_manager.scenes
+        {
+            scene.reload_scripts();
+        }
+        self.publish_objects();
+        self.apply_object_requests();
+    }
+
+    // Applies the changes scripts made to other objects during the last update, then
+    // removes and adds the objects they asked for.
+    fn apply_object_requests(&mut self)
+    {
+        let changes = object::take_object_requests();
+        for request in changes
+        {
+            self.scene_manager.apply_object_request(request);
+        }
+
+        let destroyed = scene_manager::take_destroy_requests();
+        let spawned = scene_manager::take_spawn_requests();
+        if destroyed.is_empty() && spawned.is_empty()
+        {
+            return;
+        }
+
+        // Found before destroying anything, as the object that asked may be destroyed too.
+        // Destroying objects leaves the scenes where they are.
+        let spawned: Vec<(Option<usize>, Yaml)> = spawned.into_iter()
+            .map(|(requester, properties)| (self.scene_manager.scene_of(requester), properties))
+            .collect();
+
+        for id in destroyed
+        {
+            // Already gone when destroyed twice in one update, or along with its parent.
+            self.scene_manager.destroy(id);
+        }
+
+        // Destroyed objects can no longer be found by the new objects' start callbacks.
+        self.publish_objects();
+
+        for (scene_index, properties) in spawned
+        {
+            match scene_index
+            {
+                Some(scene_index) =>
+                {
+                    let mut raylib = self.raylib.as_mut().map(|(rl, thread)| (rl, &*thread));
+                    self.scene_manager.spawn(raylib.as_mut(), scene_index, &properties);
+                }
+                None => log_warn!("game", "Could not spawn {:?}, the object that asked for it is no longer loaded.", properties),
+            }
+        }
+
+        self.publish_objects();
+    }
+
+    // Lets external functions look up objects from every loaded scene.
+    fn publish_objects(&mut self)
+    {
+        object::clear_directory();
+        for scene in &mut self.scene_manager.scenes
+        {
+            object::publish_objects(&mut scene.objects);
+        }
+
+        // Scripts convert positions with the camera of the top most scene that has one.
+        let view = self.scene_manager.drawing_scenes().iter().rev()
+            .find_map(|scene| scene.active_camera_2d().map(|camera| camera.view()));
+        object::set_active_view(view);
+    }
+
+    // Starts the scene on top of the stack.
+    fn start_scene(&mut self)
+    {
+        self.publish_objects();
+
+        if let Some(current_scene) = self.scene_manager.current_scene_mut()
+        {
+            current_scene.start();
+        }
     }
 
-    fn startup_yaml(&mut self, raylib: &mut Raylib, contents: Vec<Yaml>)
+    fn startup_yaml(&mut self, mut raylib: Option<&mut Raylib>, contents: Vec<Yaml>)
     {
         if let Yaml::String(name) = &contents[0]["name"]
         {
             self.name = name.to_string();
-            raylib.0.set_window_title(raylib.1, name);
+
+            if let Some(raylib) = &mut raylib
+            {
+                raylib.0.set_window_title(raylib.1, name);
+            }
         }
 
         if let Yaml::String(main_scene) = &contents[0]["main_scene"]
@@ -157,3 +824,31 @@ impl Game
         }
     }
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn shutdown_clears_state_left_for_the_next_game()
+    {
+        scene_manager::request_scene(SceneRequest::Pop);
+        scene_manager::request_spawn(1, Yaml::Null);
+        scene_manager::request_destroy(1);
+        object::request_object(object::ObjectRequest::Call(1, "hit".to_string(), vec![]));
+        object::request_shake(1.0, 1.0);
+        time::set_time_scale(2.0).unwrap();
+
+        let mut game = Game::new("missing.yaml");
+        game.shutdown();
+
+        assert!(scene_manager::take_scene_requests().is_empty());
+        assert!(scene_manager::take_spawn_requests().is_empty());
+        assert!(scene_manager::take_destroy_requests().is_empty());
+        assert!(object::take_object_requests().is_empty());
+        assert!(object::take_shake_requests().is_empty());
+        assert!(object::active_view().is_none());
+        assert_eq!(time::time_scale(), 1.0);
+    }
+}
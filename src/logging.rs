@@ -0,0 +1,441 @@
+use crate::log_warn;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::time::{Duration, Instant};
+use yaml_rust::Yaml;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level
+{
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level
+{
+    pub fn from_name(name: &str) -> Option<Self>
+    {
+        match name
+        {
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
+// Where log messages go and which are kept. Read from the logging section of the game yaml.
+#[derive(Clone, Debug)]
+pub struct LogConfig
+{
+    // Messages below the level are dropped, unless their target sets its own.
+    pub level: Level,
+    pub targets: HashMap<String, Level>,
+
+    pub console: bool,
+    // No file is written when empty. Once it reaches the max size it is moved to
+    // file.1, file.1 to file.2 and so on, keeping at most max_files old files.
+    pub file: String,
+    pub max_file_size: u64,
+    pub max_files: u32,
+
+    // Shows the latest messages over the game, each for overlay_duration.
+    pub overlay: bool,
+    pub overlay_lines: usize,
+    pub overlay_duration: Duration,
+}
+
+impl LogConfig
+{
+    pub fn new() -> Self
+    {
+        LogConfig
+        {
+            level: Level::Info,
+            targets: HashMap::new(),
+
+            console: true,
+            file: String::new(),
+            max_file_size: 1024 * 1024,
+            max_files: 3,
+
+            overlay: false,
+            overlay_lines: 8,
+            overlay_duration: Duration::from_secs(5),
+        }
+    }
+
+    pub fn from_yaml(logging: &Yaml) -> Self
+    {
+        let mut config = LogConfig::new();
+
+        let read_level = |value: &Yaml, key: &str| match value.as_str().and_then(Level::from_name)
+        {
+            Some(level) => Some(level),
+            None =>
+            {
+                if !value.is_badvalue()
+                {
+                    log_warn!("config", "Invalid value {:?} for {}, expected debug, info, warn or error.", value, key);
+                }
+                None
+            }
+        };
+
+        if let Some(level) = read_level(&logging["level"], "logging.level")
+        {
+            config.level = level;
+        }
+        if let Some(targets) = logging["targets"].as_hash()
+        {
+            for (target, level) in targets
+            {
+                let target = target.as_str().unwrap_or("");
+                if let Some(level) = read_level(level, &format!("logging.targets.{}", target))
+                {
+                    config.targets.insert(target.to_string(), level);
+                }
+            }
+        }
+
+        config.console = logging["console"].as_bool().unwrap_or(config.console);
+        config.file = logging["file"].as_str().unwrap_or("").to_string();
+        if let Some(size) = logging["max_file_size"].as_i64().filter(|size| *size > 0)
+        {
+            config.max_file_size = size as u64;
+        }
+        if let Some(files) = logging["max_files"].as_i64().filter(|files| *files >= 0)
+        {
+            config.max_files = files as u32;
+        }
+
+        config.overlay = logging["overlay"].as_bool().unwrap_or(config.overlay);
+        if let Some(lines) = logging["overlay_lines"].as_i64().filter(|lines| *lines > 0)
+        {
+            config.overlay_lines = lines as usize;
+        }
+        let seconds = &logging["overlay_seconds"];
+        match seconds.as_f64().or(seconds.as_i64().map(|x| x as f64))
+        {
+            Some(number) => match Duration::try_from_secs_f64(number)
+            {
+                Ok(duration) => { config.overlay_duration = duration; }
+                Err(_) => log_warn!("config", "Invalid value {} for logging.overlay_seconds, it must be a number of 0 or more.", number),
+            },
+            None => if !seconds.is_badvalue()
+            {
+                log_warn!("config", "Invalid value {:?} for logging.overlay_seconds, expected a number.", seconds);
+            }
+        }
+
+        config
+    }
+
+    fn enabled(&self, level: Level, target: &str) -> bool
+    {
+        level >= *self.targets.get(target).unwrap_or(&self.level)
+    }
+}
+
+struct Logger
+{
+    config: LogConfig,
+    file: Option<File>,
+    file_size: u64,
+    overlay: VecDeque<(Instant, Level, String)>,
+}
+
+thread_local!
+{
+    static LOGGER: RefCell<Logger> = RefCell::new(Logger
+    {
+        config: LogConfig::new(),
+        file: None,
+        file_size: 0,
+        overlay: VecDeque::new(),
+    });
+}
+
+pub fn configure(config: LogConfig)
+{
+    LOGGER.with(|logger|
+    {
+        let mut logger = logger.borrow_mut();
+        logger.file = None;
+        logger.file_size = 0;
+
+        if !config.file.is_empty()
+        {
+            match OpenOptions::new().create(true).append(true).open(&config.file)
+            {
+                Ok(file) =>
+                {
+                    logger.file_size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                    logger.file = Some(file);
+                }
+                // Can not log through the logger while it is being configured.
+                Err(error) => eprintln!("Failed to open log file {} due to {}.", config.file, error),
+            }
+        }
+
+        logger.config = config;
+    });
+}
+
+pub fn enabled(level: Level, target: &str) -> bool
+{
+    LOGGER.with(|logger| logger.borrow().config.enabled(level, target))
+}
+
+pub fn log(level: Level, target: &str, message: &str)
+{
+    LOGGER.with(|logger|
+    {
+        let mut logger = logger.borrow_mut();
+        if !logger.config.enabled(level, target)
+        {
+            return;
+        }
+
+        let line = format!("[{}] {}: {}", level.name(), target, message);
+
+        if logger.config.console
+        {
+            if level >= Level::Warn { eprintln!("{}", line); } else { println!("{}", line); }
+        }
+
+        if logger.file.is_some()
+        {
+            logger.write_file(&line);
+        }
+
+        if logger.config.overlay
+        {
+            logger.overlay.push_back((Instant::now(), level, line));
+            while logger.overlay.len() > logger.config.overlay_lines
+            {
+                logger.overlay.pop_front();
+            }
+        }
+        logger.prune_overlay();
+    });
+}
+
+impl Logger
+{
+    // Drops the overlay lines that have been shown for long enough.
+    fn prune_overlay(&mut self)
+    {
+        let lifetime = self.config.overlay_duration;
+        while self.overlay.front().map_or(false, |line| line.0.elapsed() > lifetime)
+        {
+            self.overlay.pop_front();
+        }
+    }
+
+    fn write_file(&mut self, line: &str)
+    {
+        let length = line.len() as u64 + 1;
+        if self.file_size > 0 && self.file_size + length > self.config.max_file_size
+        {
+            self.rotate();
+        }
+
+        if let Some(file) = &mut self.file
+        {
+            if writeln!(file, "{}", line).is_ok()
+            {
+                self.file_size += length;
+            }
+        }
+    }
+
+    fn rotate(&mut self)
+    {
+        self.file = None;
+        let path = &self.config.file;
+
+        if self.config.max_files == 0
+        {
+            let _ = fs::remove_file(path);
+        }
+        else
+        {
+            for index in (1..self.config.max_files).rev()
+            {
+                let _ = fs::rename(format!("{}.{}", path, index), format!("{}.{}", path, index + 1));
+            }
+            let _ = fs::rename(path, format!("{}.1", path));
+        }
+
+        self.file = File::create(path).ok();
+        self.file_size = 0;
+    }
+}
+
+// Calls the function with each message to show over the game, oldest first, along with
+// its index. Expired ones are dropped first.
+pub fn for_each_overlay_line(mut f: impl FnMut(usize, Level, &str))
+{
+    LOGGER.with(|logger|
+    {
+        let mut logger = logger.borrow_mut();
+        logger.prune_overlay();
+
+        for (index, (_, level, line)) in logger.overlay.iter().enumerate()
+        {
+            f(index, *level, line);
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log_debug
+{
+    ($target: expr, $($arg: tt)*) => { $crate::logging::log($crate::logging::Level::Debug, $target, &format!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! log_info
+{
+    ($target: expr, $($arg: tt)*) => { $crate::logging::log($crate::logging::Level::Info, $target, &format!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! log_warn
+{
+    ($target: expr, $($arg: tt)*) => { $crate::logging::log($crate::logging::Level::Warn, $target, &format!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! log_error
+{
+    ($target: expr, $($arg: tt)*) => { $crate::logging::log($crate::logging::Level::Error, $target, &format!($($arg)*)) };
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::temp_directory;
+    use yaml_rust::YamlLoader;
+
+    fn config(source: &str) -> LogConfig
+    {
+        LogConfig::from_yaml(&YamlLoader::load_from_str(source).unwrap()[0])
+    }
+
+    #[test]
+    fn levels_are_read_by_name()
+    {
+        assert_eq!(Level::from_name("debug"), Some(Level::Debug));
+        assert_eq!(Level::from_name("error"), Some(Level::Error));
+        assert_eq!(Level::from_name("verbose"), None);
+        assert!(Level::Debug < Level::Info && Level::Warn < Level::Error);
+    }
+
+    #[test]
+    fn targets_override_the_level()
+    {
+        let config = config("level: warn\ntargets:\n  script: debug\n  scene: error\n  input: loud");
+        assert_eq!(config.level, Level::Warn);
+        assert_eq!(config.targets.len(), 2);
+
+        assert!(config.enabled(Level::Debug, "script"));
+        assert!(!config.enabled(Level::Warn, "scene"));
+        assert!(config.enabled(Level::Error, "scene"));
+        assert!(!config.enabled(Level::Info, "input"));
+        assert!(config.enabled(Level::Warn, "game"));
+    }
+
+    #[test]
+    fn invalid_values_keep_defaults()
+    {
+        let config = config("level: loud\nmax_file_size: -1\nmax_files: -2\noverlay_lines: 0");
+        let defaults = LogConfig::new();
+        assert_eq!(config.level, defaults.level);
+        assert_eq!(config.max_file_size, defaults.max_file_size);
+        assert_eq!(config.max_files, defaults.max_files);
+        assert_eq!(config.overlay_lines, defaults.overlay_lines);
+    }
+
+    #[test]
+    fn overlay_seconds_must_fit_a_duration()
+    {
+        assert_eq!(config("overlay_seconds: 2.5").overlay_duration, Duration::from_millis(2500));
+        assert_eq!(config("overlay_seconds: 3").overlay_duration, Duration::from_secs(3));
+
+        let defaults = LogConfig::new();
+        for value in &[".inf", ".nan", "-1", "1.0e20", "soon"]
+        {
+            assert_eq!(config(&format!("overlay_seconds: {}", value)).overlay_duration, defaults.overlay_duration);
+        }
+    }
+
+    #[test]
+    fn files_rotate_once_full()
+    {
+        let directory = temp_directory("log_rotate");
+        let path = directory.join("game.log");
+
+        let mut config = LogConfig::new();
+        config.console = false;
+        config.file = path.to_str().unwrap().to_string();
+        config.max_file_size = 80;
+        config.max_files = 2;
+        configure(config);
+
+        // Each line is 38 bytes, so two fit in a file.
+        for index in 0..6
+        {
+            log(Level::Info, "test", &format!("line number {} of the log", index));
+        }
+        configure(LogConfig::new());
+
+        let current = fs::read_to_string(&path).unwrap();
+        let first = fs::read_to_string(format!("{}.1", path.display())).unwrap();
+        let second = fs::read_to_string(format!("{}.2", path.display())).unwrap();
+        assert!(current.contains("line number 5"));
+        assert!(first.contains("line number 3"));
+        assert!(second.contains("line number 1"));
+        assert!(!directory.join("game.log.3").exists());
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn overlay_keeps_the_latest_lines()
+    {
+        let mut config = LogConfig::new();
+        config.console = false;
+        config.overlay = true;
+        config.overlay_lines = 2;
+        configure(config);
+
+        for index in 0..5
+        {
+            log(Level::Warn, "test", &format!("warning {}", index));
+        }
+
+        let mut lines = vec![];
+        for_each_overlay_line(|index, level, line| lines.push((index, level, line.to_string())));
+        assert_eq!(lines, vec![(0, Level::Warn, "[WARN] test: warning 3".to_string()), (1, Level::Warn, "[WARN] test: warning 4".to_string())]);
+    }
+}
@@ -3,16 +3,28 @@ use downcast_rs::Downcast;
 use drython::types::ExFnRef;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use yaml_rust::Yaml;
 
 #[path="object2d.rs"]
 mod object2d;
 pub use object2d::Object2D;
 
+#[path="camera2d.rs"]
+mod camera2d;
+pub use camera2d::{Camera2D, CameraView, set_active_view, active_view, request_shake, take_shake_requests};
+
 #[path="object3d.rs"]
 mod object3d;
 pub use object3d::Object3D;
 
-use crate::drython_extensions::DrythonExRef;
+#[path="camera3d.rs"]
+mod camera3d;
+pub use camera3d::Camera3D;
+
+use crate::drython_extensions::{DrythonExRef, ExVarMap};
+use drython::types::Token;
 
 static OBJECT_COUNTER: AtomicUsize = AtomicUsize::new(1);
 pub fn generate_object_id() -> usize
@@ -27,16 +39,118 @@ pub trait TObject: Downcast + DrythonExRef + ExFnRef
     fn get_id(&self) -> usize;
 
     fn get_obj(&mut self) -> &mut Object;
+    fn get_obj_ref(&self) -> &Object;
 }
 
 impl_downcast!(TObject);
 
+// What external functions can see of objects other than their own.
+pub struct ObjectInfo
+{
+    pub name: String,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub tags: Vec<String>,
+    pub group: String,
+    // The variables the object exposes to its script, as they were when published.
+    pub vars: ExVarMap,
+}
+
+// Snapshot of every loaded object by id, rebuilt before scripts are run.
+thread_local!
+{
+    static OBJECT_DIRECTORY: RefCell<HashMap<usize, ObjectInfo>> = RefCell::new(HashMap::new());
+}
+
+pub fn clear_directory()
+{
+    OBJECT_DIRECTORY.with(|directory| directory.borrow_mut().clear());
+}
+
+pub fn publish_objects(objects: &mut Vec<Box<dyn TObject>>)
+{
+    OBJECT_DIRECTORY.with(|directory|
+    {
+        let mut directory = directory.borrow_mut();
+        for object in objects
+        {
+            let vars = object.get_drython_vars();
+            let obj = object.get_obj_ref();
+            directory.insert(obj.id, ObjectInfo
+            {
+                name: obj.name.clone(),
+                parent: obj.parent,
+                children: obj.children.clone(),
+                tags: obj.tags.clone(),
+                group: obj.group.clone(),
+                vars,
+            });
+        }
+    });
+}
+
+pub fn with_object_info<R>(id: usize, f: impl FnOnce(&ObjectInfo) -> R) -> Option<R>
+{
+    OBJECT_DIRECTORY.with(|directory| directory.borrow().get(&id).map(f))
+}
+
+// Ids of every published object the filter accepts, lowest first.
+pub fn find_objects(filter: impl Fn(&ObjectInfo) -> bool) -> Vec<usize>
+{
+    OBJECT_DIRECTORY.with(|directory|
+    {
+        let mut ids: Vec<usize> = directory.borrow().iter()
+            .filter(|(_, info)| filter(info))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        ids
+    })
+}
+
+// Changes scripts make to other objects. They are applied in order once the update finishes,
+// so every script sees the same state of the other objects during an update.
+pub enum ObjectRequest
+{
+    SetVar(usize, String, Token),
+    Call(usize, String, Vec<Token>),
+}
+
+thread_local!
+{
+    static OBJECT_REQUESTS: RefCell<Vec<ObjectRequest>> = RefCell::new(vec![]);
+}
+
+pub fn request_object(request: ObjectRequest)
+{
+    OBJECT_REQUESTS.with(|requests| requests.borrow_mut().push(request));
+}
+
+pub fn take_object_requests() -> Vec<ObjectRequest>
+{
+    OBJECT_REQUESTS.with(|requests| requests.borrow_mut().drain(..).collect())
+}
+
 pub struct Object
 {
     pub name: String,
     pub id: usize,
 
-    inputs: Vec<(String, String)>,
+    // Ids of the parent and child objects in the same scene.
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+
+    // Tags and a group scripts can look objects up by.
+    pub tags: Vec<String>,
+    pub group: String,
+
+    // Type name, script paths in the order they run and yaml the object was loaded with, kept for saving.
+    pub type_name: String,
+    pub scripts: Vec<String>,
+    pub loaded_properties: Yaml,
+
+    // Actions and the script function they call.
+    pub inputs: Vec<(String, String)>,
 }
 
 impl Object
@@ -61,6 +175,16 @@ impl TObject for Object
             name: String::new(),
             id: generate_object_id(),
 
+            parent: None,
+            children: Vec::new(),
+
+            tags: Vec::new(),
+            group: String::new(),
+
+            type_name: String::new(),
+            scripts: vec![],
+            loaded_properties: Yaml::BadValue,
+
             inputs: Vec::new(),
         }
     }
@@ -79,6 +203,11 @@ impl TObject for Object
     {
         self
     }
+
+    fn get_obj_ref(&self) -> &Object
+    {
+        self
+    }
 }
 
 impl ExFnRef for Box<dyn TObject>
@@ -96,6 +225,21 @@ impl ExFnRef for Object2D
     fn as_any(&self) -> &dyn std::any::Any {self}
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any {self}
 }
+impl ExFnRef for Object3D
+{
+    fn as_any(&self) -> &dyn std::any::Any {self}
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {self}
+}
+impl ExFnRef for Camera3D
+{
+    fn as_any(&self) -> &dyn std::any::Any {self}
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {self}
+}
+impl ExFnRef for Camera2D
+{
+    fn as_any(&self) -> &dyn std::any::Any {self}
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {self}
+}
 
 #[macro_export]
 macro_rules! generate_get_name
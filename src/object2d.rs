@@ -1,6 +1,8 @@
 use crate::{object::TObject, generate_get_name, generate_get_id};
 
-use raylib::math::Vector2;
+use raylib::math::{Vector2, Rectangle};
+use raylib::color::Color;
+use raylib::prelude::RaylibDraw;
 use crate::transform::Transform2D;
 use raylib::texture::Texture2D;
 use crate::object::Object;
@@ -10,7 +12,27 @@ pub struct Object2D
     pub object: Object,
 
     pub sprite: Option<Texture2D>,
+    pub sprite_path: String,
     pub transform: Transform2D,
+    // Position at the start of the last fixed update, used to interpolate drawing.
+    pub previous_pos: Vector2,
+
+    // Pivot for rotation and scaling, from (0, 0) top left to (1, 1) bottom right of the sprite.
+    pub origin: Vector2,
+    pub tint: Color,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // Part of the sprite to draw, the whole sprite when the size is 0.
+    pub source_rect: Rectangle,
+    pub visible: bool,
+
+    // Draw order, render layers first then z index within a layer. Higher is drawn on top.
+    pub layer: String,
+    pub z_index: i32,
+
+    // Drawn in the tint colour when not empty.
+    pub text: String,
+    pub font_size: i32,
 }
 
 impl TObject for Object2D
@@ -21,12 +43,27 @@ impl TObject for Object2D
         {
             object: Object::new(),
             sprite: None,
+            sprite_path: String::new(),
             transform: Transform2D
             {
                 pos: Vector2::zero(),
                 rot: 0.0,
-                scale: Vector2::zero(),
+                scale: Vector2::one(),
             },
+            previous_pos: Vector2::zero(),
+
+            origin: Vector2::zero(),
+            tint: Color::WHITE,
+            flip_x: false,
+            flip_y: false,
+            source_rect: Rectangle::new(0.0, 0.0, 0.0, 0.0),
+            visible: true,
+
+            layer: String::new(),
+            z_index: 0,
+
+            text: String::new(),
+            font_size: 20,
         }
     }
 
@@ -37,4 +74,53 @@ impl TObject for Object2D
     {
         &mut self.object
     }
+
+    fn get_obj_ref(&self) -> &Object
+    {
+        &self.object
+    }
+}
+
+impl Object2D
+{
+    // Draws the object where it is, without moving in from where it was last fixed update.
+    // Used once per fixed update, and whenever the object is created or rebuilt.
+    pub fn reset_interpolation(&mut self)
+    {
+        self.previous_pos = self.transform.pos;
+    }
+
+    // Draws the sprite and text with the given world transform.
+    pub fn draw(&self, d: &mut impl RaylibDraw, world: &Transform2D)
+    {
+        if !self.visible
+        {
+            return;
+        }
+
+        if let Some(texture) = &self.sprite
+        {
+            let mut source = self.source_rect;
+            if source.width == 0.0 || source.height == 0.0
+            {
+                source = Rectangle::new(0.0, 0.0, texture.width as f32, texture.height as f32);
+            }
+
+            let dest = Rectangle::new(world.pos.x, world.pos.y,
+                source.width * world.scale.x, source.height * world.scale.y);
+            let origin = Vector2::new(self.origin.x * dest.width, self.origin.y * dest.height);
+
+            // Negative source sizes flip the sprite.
+            if self.flip_x { source.width = -source.width; }
+            if self.flip_y { source.height = -source.height; }
+
+            d.draw_texture_pro(texture, source, dest, origin, world.rot, self.tint);
+        }
+
+        if !self.text.is_empty()
+        {
+            let font_size = (self.font_size as f32 * world.scale.y) as i32;
+            d.draw_text(&self.text, world.pos.x as i32, world.pos.y as i32, font_size, self.tint);
+        }
+    }
 }
@@ -1,9 +1,79 @@
+use crate::{object::TObject, generate_get_name, generate_get_id};
+
+use raylib::math::{Vector3, Quaternion};
+use raylib::color::Color;
+use raylib::models::Model;
+use raylib::prelude::RaylibDraw3D;
+use raylib::texture::Texture2D;
 use crate::transform::Transform3D;
 use crate::object::Object;
 
 pub struct Object3D
 {
-    object: Object,
+    pub object: Object,
+
+    pub transform: Transform3D,
+
+    pub model: Option<Model>,
+    pub model_path: String,
+    // Texture used as the model's albedo map.
+    pub texture: Option<Texture2D>,
+    pub texture_path: String,
+    pub tint: Color,
+    pub visible: bool,
+}
+
+impl TObject for Object3D
+{
+    fn new() -> Self
+    {
+        Object3D
+        {
+            object: Object::new(),
+            transform: Transform3D
+            {
+                pos: Vector3::zero(),
+                rot: Quaternion::identity(),
+                scale: Vector3::one(),
+            },
+
+            model: None,
+            model_path: String::new(),
+            texture: None,
+            texture_path: String::new(),
+            tint: Color::WHITE,
+            visible: true,
+        }
+    }
+
+    generate_get_name!();
+    generate_get_id!();
+
+    fn get_obj(&mut self) -> &mut Object
+    {
+        &mut self.object
+    }
+
+    fn get_obj_ref(&self) -> &Object
+    {
+        &self.object
+    }
+}
+
+impl Object3D
+{
+    // Draws the model with the given world transform, inside of a 3d mode.
+    pub fn draw(&self, d: &mut impl RaylibDraw3D, world: &Transform3D)
+    {
+        if !self.visible
+        {
+            return;
+        }
 
-    transform: Transform3D,
+        if let Some(model) = &self.model
+        {
+            let (axis, angle) = world.rot.to_axis_angle();
+            d.draw_model_ex(model, world.pos, axis, angle.to_degrees(), world.scale, self.tint);
+        }
+    }
 }
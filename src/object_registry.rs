@@ -0,0 +1,492 @@
+use crate::{log_error, log_warn};
+use crate::object::{TObject, Object, Object2D, Object3D, Camera2D, Camera3D};
+use crate::scene_manager::scene::Scene;
+use crate::scene_manager::{read_f32, read_vector2, read_color, read_rectangle};
+use crate::Raylib;
+
+use raylib::prelude::{Vector2, Vector3, Quaternion, Color, Rectangle, MaterialMapType, RaylibMaterial, RaylibModel};
+use std::collections::HashMap;
+use yaml_rust::Yaml;
+use yaml_rust::yaml::Hash;
+
+// Builds an object of one type from its yaml properties. The name, script, type and
+// children keys are handled by the scene manager for every type.
+pub type ObjectLoader = fn(Option<&mut Raylib>, &Scene, &Yaml) -> Box<dyn TObject>;
+
+// Writes an object's current properties in the same format its loader reads.
+pub type ObjectSaver = fn(&dyn TObject, &mut Hash);
+
+// Maps the type names used in scene yaml to their loaders and savers.
+#[derive(Clone)]
+pub struct ObjectRegistry
+{
+    types: HashMap<String, (ObjectLoader, ObjectSaver)>,
+}
+
+impl ObjectRegistry
+{
+    pub fn new() -> Self
+    {
+        let mut registry = ObjectRegistry
+        {
+            types: HashMap::new(),
+        };
+
+        registry.register("object2d", load_object_2d, save_object_2d);
+        registry.register("text", load_text, save_object_2d);
+        registry.register("camera", load_camera_2d, save_camera_2d);
+        registry.register("object3d", load_object_3d, save_object_3d);
+        registry.register("camera3d", load_camera_3d, save_camera_3d);
+        registry.register("sound", load_sound, save_sound);
+
+        registry
+    }
+
+    // Adds a new object type, or replaces an existing one.
+    pub fn register(&mut self, type_name: &str, loader: ObjectLoader, saver: ObjectSaver)
+    {
+        self.types.insert(type_name.to_string(), (loader, saver));
+    }
+
+    pub fn get(&self, type_name: &str) -> Option<ObjectLoader>
+    {
+        self.types.get(type_name).map(|x| x.0)
+    }
+
+    pub fn get_saver(&self, type_name: &str) -> Option<ObjectSaver>
+    {
+        self.types.get(type_name).map(|x| x.1)
+    }
+}
+
+fn key(name: &str) -> Yaml
+{
+    Yaml::String(name.to_string())
+}
+
+// The shortest text that reads back as exactly the same f32. Debug formatting keeps the
+// decimal point, so whole numbers still load as floats.
+pub fn write_f32(value: f32) -> Yaml
+{
+    Yaml::Real(format!("{:?}", value))
+}
+
+pub fn write_vector2(vec: Vector2) -> Yaml
+{
+    let mut hash = Hash::new();
+    hash.insert(key("x"), write_f32(vec.x));
+    hash.insert(key("y"), write_f32(vec.y));
+    Yaml::Hash(hash)
+}
+
+pub fn write_vector3(vec: Vector3) -> Yaml
+{
+    let mut hash = Hash::new();
+    hash.insert(key("x"), write_f32(vec.x));
+    hash.insert(key("y"), write_f32(vec.y));
+    hash.insert(key("z"), write_f32(vec.z));
+    Yaml::Hash(hash)
+}
+
+pub fn write_quaternion(rot: Quaternion) -> Yaml
+{
+    let mut hash = Hash::new();
+    hash.insert(key("x"), write_f32(rot.x));
+    hash.insert(key("y"), write_f32(rot.y));
+    hash.insert(key("z"), write_f32(rot.z));
+    hash.insert(key("w"), write_f32(rot.w));
+    Yaml::Hash(hash)
+}
+
+pub fn write_color(color: Color) -> Yaml
+{
+    let mut hash = Hash::new();
+    hash.insert(key("r"), Yaml::Integer(color.r as i64));
+    hash.insert(key("g"), Yaml::Integer(color.g as i64));
+    hash.insert(key("b"), Yaml::Integer(color.b as i64));
+    hash.insert(key("a"), Yaml::Integer(color.a as i64));
+    Yaml::Hash(hash)
+}
+
+pub fn write_rectangle(rect: Rectangle) -> Yaml
+{
+    let mut hash = Hash::new();
+    hash.insert(key("x"), write_f32(rect.x));
+    hash.insert(key("y"), write_f32(rect.y));
+    hash.insert(key("width"), write_f32(rect.width));
+    hash.insert(key("height"), write_f32(rect.height));
+    Yaml::Hash(hash)
+}
+
+// Empty paths are left out, they were never set.
+fn write_path(hash: &mut Hash, name: &str, path: &str)
+{
+    if path.is_empty()
+    {
+        hash.remove(&key(name));
+    }
+    else
+    {
+        hash.insert(key(name), Yaml::String(path.to_string()));
+    }
+}
+
+pub fn save_object_2d(object: &dyn TObject, hash: &mut Hash)
+{
+    if let Some(object2d) = object.downcast_ref::<Object2D>()
+    {
+        write_path(hash, "sprite", &object2d.sprite_path);
+        hash.insert(key("pos"), write_vector2(object2d.transform.pos));
+        hash.insert(key("rot"), write_f32(object2d.transform.rot));
+        hash.insert(key("scale"), write_vector2(object2d.transform.scale));
+        hash.insert(key("origin"), write_vector2(object2d.origin));
+        hash.insert(key("tint"), write_color(object2d.tint));
+        hash.insert(key("flip_x"), Yaml::Boolean(object2d.flip_x));
+        hash.insert(key("flip_y"), Yaml::Boolean(object2d.flip_y));
+        hash.insert(key("source"), write_rectangle(object2d.source_rect));
+        hash.insert(key("visible"), Yaml::Boolean(object2d.visible));
+        write_path(hash, "layer", &object2d.layer);
+        hash.insert(key("z_index"), Yaml::Integer(object2d.z_index as i64));
+        hash.insert(key("text"), Yaml::String(object2d.text.clone()));
+        hash.insert(key("font_size"), Yaml::Integer(object2d.font_size as i64));
+    }
+}
+
+pub fn save_camera_2d(object: &dyn TObject, hash: &mut Hash)
+{
+    if let Some(camera) = object.downcast_ref::<Camera2D>()
+    {
+        hash.insert(key("offset"), write_vector2(camera.offset));
+        hash.insert(key("target"), write_vector2(camera.target));
+        hash.insert(key("rotation"), write_f32(camera.rotation));
+        hash.insert(key("zoom"), write_f32(camera.zoom));
+        hash.insert(key("active"), Yaml::Boolean(camera.active));
+        hash.insert(key("follow"), Yaml::String(camera.follow.clone()));
+        hash.insert(key("smoothing"), write_f32(camera.smoothing));
+        hash.insert(key("dead_zone"), write_vector2(camera.dead_zone));
+        hash.insert(key("bounds"), write_rectangle(camera.bounds));
+    }
+}
+
+pub fn save_object_3d(object: &dyn TObject, hash: &mut Hash)
+{
+    if let Some(object3d) = object.downcast_ref::<Object3D>()
+    {
+        hash.insert(key("pos"), write_vector3(object3d.transform.pos));
+        hash.insert(key("rot"), write_quaternion(object3d.transform.rot));
+        hash.insert(key("scale"), write_vector3(object3d.transform.scale));
+        write_path(hash, "model", &object3d.model_path);
+        write_path(hash, "texture", &object3d.texture_path);
+        hash.insert(key("tint"), write_color(object3d.tint));
+        hash.insert(key("visible"), Yaml::Boolean(object3d.visible));
+    }
+}
+
+pub fn save_camera_3d(object: &dyn TObject, hash: &mut Hash)
+{
+    if let Some(camera) = object.downcast_ref::<Camera3D>()
+    {
+        hash.insert(key("position"), write_vector3(camera.position));
+        hash.insert(key("target"), write_vector3(camera.target));
+        hash.insert(key("up"), write_vector3(camera.up));
+        hash.insert(key("fovy"), write_f32(camera.fovy));
+        hash.insert(key("projection"), key(if camera.orthographic { "orthographic" } else { "perspective" }));
+        hash.insert(key("active"), Yaml::Boolean(camera.active));
+    }
+}
+
+fn params(unloaded: &Yaml) -> impl Iterator<Item = (&str, &Yaml)>
+{
+    unloaded.as_hash().into_iter()
+        .flat_map(|hash| hash.iter())
+        .filter_map(|param| param.0.as_str().map(|name| (name, param.1)))
+}
+
+pub fn load_object_2d(mut raylib: Option<&mut Raylib>, scene: &Scene, unloaded: &Yaml) -> Box<dyn TObject>
+{
+    let mut new_obj = Object2D::new();
+
+    for (param_name, value) in params(unloaded)
+    {
+        match param_name
+        {
+            "sprite" => handle_sprite(&mut new_obj, raylib.as_deref_mut(), value),
+            "pos" => { new_obj.transform.pos = read_vector2(value, Vector2::zero()); },
+            "rot" => { new_obj.transform.rot = read_f32(value).unwrap_or(0.0); },
+            "scale" => { new_obj.transform.scale = read_vector2(value, Vector2::one()); },
+            "origin" => { new_obj.origin = read_vector2(value, Vector2::zero()); },
+            "tint" => { new_obj.tint = read_color(value); },
+            "flip_x" => { new_obj.flip_x = value.as_bool().unwrap_or(false); },
+            "flip_y" => { new_obj.flip_y = value.as_bool().unwrap_or(false); },
+            "source" => { new_obj.source_rect = read_rectangle(value); },
+            "visible" => { new_obj.visible = value.as_bool().unwrap_or(true); },
+            "layer" =>
+            {
+                new_obj.layer = value.as_str().unwrap_or("").to_string();
+                if !scene.render_layers.contains(&new_obj.layer)
+                {
+                    log_warn!("objects", "Render layer {} is not declared in {}, the bottom layer will be used.", new_obj.layer, scene.scene_path);
+                }
+            },
+            "z_index" => { new_obj.z_index = value.as_i64().unwrap_or(0) as i32; },
+            "text" => { new_obj.text = value.as_str().unwrap_or("").to_string(); },
+            "font_size" => { new_obj.font_size = value.as_i64().unwrap_or(20) as i32; },
+            _ => ()
+        }
+    }
+    new_obj.reset_interpolation();
+
+    Box::new(new_obj)
+}
+
+// Text is an Object2D without a sprite, the tint is used as the text colour.
+pub fn load_text(raylib: Option<&mut Raylib>, scene: &Scene, unloaded: &Yaml) -> Box<dyn TObject>
+{
+    let mut new_obj = load_object_2d(raylib, scene, unloaded);
+    if let Some(object2d) = new_obj.downcast_mut::<Object2D>()
+    {
+        if unloaded["tint"].is_badvalue()
+        {
+            object2d.tint = Color::BLACK;
+        }
+    }
+
+    new_obj
+}
+
+pub fn load_camera_2d(_raylib: Option<&mut Raylib>, _scene: &Scene, unloaded: &Yaml) -> Box<dyn TObject>
+{
+    let mut new_camera = Camera2D::new();
+
+    for (param_name, value) in params(unloaded)
+    {
+        match param_name
+        {
+            "offset" => { new_camera.offset = read_vector2(value, Vector2::zero()); },
+            "target" => { new_camera.target = read_vector2(value, Vector2::zero()); },
+            "rotation" => { new_camera.rotation = read_f32(value).unwrap_or(0.0); },
+            "zoom" => { new_camera.zoom = read_f32(value).unwrap_or(1.0); },
+            "active" => { new_camera.active = value.as_bool().unwrap_or(true); },
+            "follow" => { new_camera.follow = value.as_str().unwrap_or("").to_string(); },
+            "smoothing" => { new_camera.smoothing = read_f32(value).unwrap_or(0.0); },
+            "dead_zone" => { new_camera.dead_zone = read_vector2(value, Vector2::zero()); },
+            "bounds" => { new_camera.bounds = read_rectangle(value); },
+            _ => ()
+        }
+    }
+    new_camera.reset_interpolation();
+
+    Box::new(new_camera)
+}
+
+// Reads {x, y, z}, missing components use the default.
+pub fn read_vector3(value: &Yaml, default: Vector3) -> Vector3
+{
+    Vector3::new(
+        read_f32(&value["x"]).unwrap_or(default.x),
+        read_f32(&value["y"]).unwrap_or(default.y),
+        read_f32(&value["z"]).unwrap_or(default.z))
+}
+
+pub fn load_object_3d(mut raylib: Option<&mut Raylib>, _scene: &Scene, unloaded: &Yaml) -> Box<dyn TObject>
+{
+    let mut new_obj = Object3D::new();
+
+    for (param_name, value) in params(unloaded)
+    {
+        match param_name
+        {
+            "pos" => { new_obj.transform.pos = read_vector3(value, Vector3::zero()); },
+            // Euler angles in degrees, or a quaternion when w is given.
+            "rot" =>
+            {
+                new_obj.transform.rot = match read_f32(&value["w"])
+                {
+                    Some(w) =>
+                    {
+                        let xyz = read_vector3(value, Vector3::zero());
+                        Quaternion::new(xyz.x, xyz.y, xyz.z, w)
+                    }
+                    None =>
+                    {
+                        let euler = read_vector3(value, Vector3::zero());
+                        Quaternion::from_euler(euler.x.to_radians(), euler.y.to_radians(), euler.z.to_radians())
+                    }
+                };
+            },
+            "scale" => { new_obj.transform.scale = read_vector3(value, Vector3::one()); },
+            "model" => handle_model(&mut new_obj, raylib.as_deref_mut(), value),
+            "tint" => { new_obj.tint = read_color(value); },
+            "visible" => { new_obj.visible = value.as_bool().unwrap_or(true); },
+            _ => ()
+        }
+    }
+
+    // The texture goes on the model's material, so it is loaded once the model is.
+    if !unloaded["texture"].is_badvalue()
+    {
+        handle_model_texture(&mut new_obj, raylib.as_deref_mut(), &unloaded["texture"]);
+    }
+
+    Box::new(new_obj)
+}
+
+// Sounds are not played yet, playing them needs an audio device, which raylib opens separately
+// from the window. They load as plain objects that keep their properties, so scripts can still
+// find them and saving the scene writes them back as they were. Games can register their own.
+pub fn load_sound(_raylib: Option<&mut Raylib>, scene: &Scene, unloaded: &Yaml) -> Box<dyn TObject>
+{
+    log_warn!("scene", "Object type \"sound\" of {} in {} is not supported yet, it is kept but will not play.",
+        unloaded["name"].as_str().unwrap_or("an object"), scene.scene_path);
+    Box::new(Object::new())
+}
+
+// Every key of a sound is kept from the loaded yaml.
+pub fn save_sound(_object: &dyn TObject, _hash: &mut Hash)
+{
+}
+
+pub fn load_camera_3d(_raylib: Option<&mut Raylib>, _scene: &Scene, unloaded: &Yaml) -> Box<dyn TObject>
+{
+    let mut new_camera = Camera3D::new();
+
+    for (param_name, value) in params(unloaded)
+    {
+        match param_name
+        {
+            "position" => { new_camera.position = read_vector3(value, new_camera.position); },
+            "target" => { new_camera.target = read_vector3(value, Vector3::zero()); },
+            "up" => { new_camera.up = read_vector3(value, Vector3::up()); },
+            "fovy" => { new_camera.fovy = read_f32(value).unwrap_or(45.0); },
+            "projection" =>
+            {
+                match value.as_str()
+                {
+                    Some("perspective") => { new_camera.orthographic = false; }
+                    Some("orthographic") => { new_camera.orthographic = true; }
+                    _ => log_warn!("objects", "Invalid camera projection {:?}, expected perspective or orthographic.", value),
+                }
+            },
+            "active" => { new_camera.active = value.as_bool().unwrap_or(true); },
+            _ => ()
+        }
+    }
+
+    Box::new(new_camera)
+}
+
+// The texture file an object draws with, relative to the assets folder.
+pub fn texture_path(object: &dyn TObject) -> Option<&str>
+{
+    if let Some(object2d) = object.downcast_ref::<Object2D>()
+    {
+        return Some(&object2d.sprite_path).filter(|path| !path.is_empty()).map(|path| path.as_str());
+    }
+    if let Some(object3d) = object.downcast_ref::<Object3D>()
+    {
+        return Some(&object3d.texture_path).filter(|path| !path.is_empty()).map(|path| path.as_str());
+    }
+
+    None
+}
+
+// Loads the object's texture again from its file, replacing the old one.
+pub fn reload_texture(object: &mut dyn TObject, raylib: Option<&mut Raylib>)
+{
+    if let Some(object2d) = object.downcast_mut::<Object2D>()
+    {
+        let path = Yaml::String(object2d.sprite_path.clone());
+        handle_sprite(object2d, raylib, &path);
+    }
+    else if let Some(object3d) = object.downcast_mut::<Object3D>()
+    {
+        let path = Yaml::String(object3d.texture_path.clone());
+        handle_model_texture(object3d, raylib, &path);
+    }
+}
+
+// Models can be any format raylib loads, such as obj or gltf. Headless only keeps the path.
+fn handle_model(new_obj: &mut Object3D, raylib: Option<&mut Raylib>, value: &Yaml)
+{
+    if let Some(file_name) = value.as_str()
+    {
+        new_obj.model_path = file_name.to_string();
+
+        if let Some(raylib) = raylib
+        {
+            match raylib.0.load_model(&raylib.1, &format!("assets/{}", file_name))
+            {
+                Ok(model) => { new_obj.model = Some(model); }
+                Err(error) => log_error!("objects", "Failed to load model {} due to {}.", file_name, error)
+            }
+        }
+    }
+    else { log_warn!("objects", "Invalid model file {:?}.", value); }
+}
+
+fn handle_model_texture(new_obj: &mut Object3D, raylib: Option<&mut Raylib>, value: &Yaml)
+{
+    if let Some(file_name) = value.as_str()
+    {
+        new_obj.texture_path = file_name.to_string();
+
+        if let Some(raylib) = raylib
+        {
+            match raylib.0.load_texture(&raylib.1, &format!("assets/{}", file_name))
+            {
+                Ok(texture) =>
+                {
+                    if let Some(model) = &mut new_obj.model
+                    {
+                        for material in model.materials_mut()
+                        {
+                            material.set_material_texture(MaterialMapType::MAP_ALBEDO, &texture);
+                        }
+                    }
+                    new_obj.texture = Some(texture);
+                }
+                Err(error) => log_error!("objects", "Failed to load image {} due to {}.", file_name, error)
+            }
+        }
+    }
+    else { log_warn!("objects", "Invalid texture file {:?}.", value); }
+}
+
+// Without a raylib context (headless) the texture is left unloaded as a placeholder,
+// only the path is kept.
+fn handle_sprite(new_obj: &mut Object2D, raylib: Option<&mut Raylib>, object1: &Yaml)
+{
+    if let Some(file_name) = object1.as_str()
+    {
+        new_obj.sprite_path = file_name.to_string();
+
+        if let Some(raylib) = raylib
+        {
+            let asset_location: &str = &format!("assets/{}", file_name);
+            match raylib.0.load_texture(&raylib.1, asset_location)
+            {
+                Ok(image) => { new_obj.sprite = Some(image); }
+                Err(error) => log_error!("objects", "Failed to load image {} due to {}.", file_name, error)
+            }
+        }
+    }
+    else { log_warn!("objects", "Invalid sprite file {:?}.", object1); }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn write_f32_is_short_and_exact()
+    {
+        assert_eq!(write_f32(0.1), Yaml::Real("0.1".to_string()));
+        assert_eq!(write_f32(2.0), Yaml::Real("2.0".to_string()));
+
+        for value in [0.1, -3.75, 1.0e-7, 123456.79, std::f32::consts::PI]
+        {
+            assert_eq!(read_f32(&write_f32(value)), Some(value));
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::cell::Cell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Seedable random numbers for scripts, so replays can reproduce a session.
+thread_local!
+{
+    static SEED: Cell<u64> = Cell::new(0);
+    static STATE: Cell<u64> = Cell::new(1);
+}
+
+pub fn seed() -> u64
+{
+    SEED.with(|seed| seed.get())
+}
+
+pub fn set_seed(seed: u64)
+{
+    SEED.with(|current| current.set(seed));
+    // Xorshift gets stuck on 0.
+    STATE.with(|state| state.set(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed }));
+}
+
+pub fn seed_from_time() -> u64
+{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(1)
+}
+
+// Xorshift64*.
+pub fn next_u64() -> u64
+{
+    STATE.with(|state|
+    {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+// From 0 up to, but not including, 1.
+pub fn next_f32() -> f32
+{
+    (next_u64() >> 40) as f32 / (1u64 << 24) as f32
+}
+
+pub fn range_f32(min: f32, max: f32) -> f32
+{
+    min + (max - min) * next_f32()
+}
+
+// From min up to and including max.
+pub fn range_i32(min: i32, max: i32) -> i32
+{
+    if max <= min
+    {
+        return min;
+    }
+
+    let span = (max as i64 - min as i64 + 1) as u64;
+    (min as i64 + (next_u64() % span) as i64) as i32
+}
@@ -0,0 +1,217 @@
+use crate::log_warn;
+use crate::scene_manager::object_registry::write_f32;
+use drython::types::Token;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+use yaml_rust::yaml::Hash;
+
+const SAVE_VERSION: i64 = 1;
+
+// Values scripts keep between sessions, one file per save slot.
+struct SaveData
+{
+    directory: PathBuf,
+    slot: u32,
+    values: Hash,
+}
+
+thread_local!
+{
+    static SAVE_DATA: RefCell<SaveData> = RefCell::new(SaveData
+    {
+        directory: PathBuf::new(),
+        slot: 0,
+        values: Hash::new(),
+    });
+}
+
+// Where the platform keeps per user application data.
+fn user_data_directory() -> PathBuf
+{
+    if let Some(data) = env::var_os("XDG_DATA_HOME").or_else(|| env::var_os("APPDATA"))
+    {
+        return PathBuf::from(data);
+    }
+
+    match env::var_os("HOME")
+    {
+        Some(home) => Path::new(&home).join(".local").join("share"),
+        None => PathBuf::from("."),
+    }
+}
+
+// Relative directories are placed inside the user's data directory.
+pub fn set_directory(directory: &str)
+{
+    let path = Path::new(directory);
+    let directory = if path.is_absolute() { path.to_path_buf() } else { user_data_directory().join(path) };
+
+    SAVE_DATA.with(|data| data.borrow_mut().directory = directory);
+    if let Err(error) = load_slot(0)
+    {
+        log_warn!("save", "Could not load save slot 0: {}", error);
+    }
+}
+
+pub fn slot() -> u32
+{
+    SAVE_DATA.with(|data| data.borrow().slot)
+}
+
+fn slot_path(directory: &Path, slot: u32) -> PathBuf
+{
+    directory.join(format!("slot_{}.yaml", slot))
+}
+
+// Switches to another slot, starting it empty if it has never been saved. A slot that
+// fails to load leaves the current slot and its values as they were.
+pub fn load_slot(slot: u32) -> Result<(), String>
+{
+    let path = SAVE_DATA.with(|data| slot_path(&data.borrow().directory, slot));
+    let values = read_slot(&path)?;
+
+    SAVE_DATA.with(|data|
+    {
+        let mut data = data.borrow_mut();
+        data.slot = slot;
+        data.values = values;
+    });
+    Ok(())
+}
+
+fn read_slot(path: &Path) -> Result<Hash, String>
+{
+    if !path.exists()
+    {
+        return Ok(Hash::new());
+    }
+
+    let file = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let yaml = YamlLoader::load_from_str(&file).map_err(|error| error.to_string())?;
+    let doc = yaml.first().ok_or("The save file is empty.")?;
+
+    if doc["version"].as_i64() != Some(SAVE_VERSION)
+    {
+        return Err(format!("Unsupported save version {:?} in {}.", doc["version"], path.display()));
+    }
+
+    Ok(doc["values"].as_hash().cloned().unwrap_or_default())
+}
+
+pub fn load_value(key: &str) -> Option<Token>
+{
+    SAVE_DATA.with(|data| data.borrow().values.get(&Yaml::String(key.to_string())).and_then(yaml_to_token))
+}
+
+// Stores the value and writes the slot straight away.
+pub fn save_value(key: &str, value: &Token) -> Result<(), String>
+{
+    let value = token_to_yaml(value).ok_or(format!("{} can not be saved, only numbers, strings, bools and collections can.", key))?;
+
+    SAVE_DATA.with(|data|
+    {
+        let mut data = data.borrow_mut();
+        data.values.insert(Yaml::String(key.to_string()), value);
+        write_slot(&data)
+    })
+}
+
+// Written to a temporary file first and renamed over the old one, so a crash
+// mid write never leaves a half written save behind.
+fn write_slot(data: &SaveData) -> Result<(), String>
+{
+    let mut doc = Hash::new();
+    doc.insert(Yaml::String("version".to_string()), Yaml::Integer(SAVE_VERSION));
+    doc.insert(Yaml::String("values".to_string()), Yaml::Hash(data.values.clone()));
+
+    let mut out = String::new();
+    YamlEmitter::new(&mut out).dump(&Yaml::Hash(doc)).map_err(|error| format!("{:?}", error))?;
+
+    fs::create_dir_all(&data.directory).map_err(|error| error.to_string())?;
+    let path = slot_path(&data.directory, data.slot);
+    let temp_path = path.with_extension("yaml.tmp");
+    fs::write(&temp_path, out).map_err(|error| error.to_string())?;
+    fs::rename(&temp_path, &path).map_err(|error| error.to_string())
+}
+
+fn token_to_yaml(token: &Token) -> Option<Yaml>
+{
+    match token
+    {
+        Token::Int(value) => Some(Yaml::Integer(*value as i64)),
+        // Debug formatting keeps the decimal point, so it loads back as a float.
+        Token::Float(value) => Some(write_f32(*value)),
+        Token::Bool(value) => Some(Yaml::Boolean(*value)),
+        Token::String(value) => Some(Yaml::String(value.clone())),
+        Token::Collection(values) => values.iter().map(token_to_yaml).collect::<Option<Vec<Yaml>>>().map(Yaml::Array),
+        _ => None,
+    }
+}
+
+fn yaml_to_token(yaml: &Yaml) -> Option<Token>
+{
+    match yaml
+    {
+        Yaml::Integer(value) => Some(Token::Int(*value as i32)),
+        Yaml::Real(value) => value.parse::<f64>().ok().map(|value| Token::Float(value as f32)),
+        Yaml::Boolean(value) => Some(Token::Bool(*value)),
+        Yaml::String(value) => Some(Token::String(value.clone())),
+        Yaml::Array(values) => values.iter().map(yaml_to_token).collect::<Option<Vec<Token>>>().map(Token::Collection),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::temp_directory;
+
+    #[test]
+    fn values_round_trip_through_the_slot_file()
+    {
+        let directory = temp_directory("save_round_trip");
+        set_directory(directory.to_str().unwrap());
+
+        let values = vec!
+        [
+            ("int", Token::Int(-4)),
+            ("float", Token::Float(0.1)),
+            ("bool", Token::Bool(true)),
+            ("string", Token::String("hello".to_string())),
+            ("list", Token::Collection(vec![Token::Int(1), Token::String("two".to_string())])),
+        ];
+        for (key, value) in &values
+        {
+            save_value(key, value).unwrap();
+        }
+
+        load_slot(1).unwrap();
+        assert!(load_value("int").is_none());
+        load_slot(0).unwrap();
+        for (key, value) in &values
+        {
+            assert_eq!(format!("{:?}", load_value(key)), format!("{:?}", Some(value)));
+        }
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn failed_load_keeps_the_current_slot()
+    {
+        let directory = temp_directory("save_failed_load");
+        set_directory(directory.to_str().unwrap());
+        save_value("coins", &Token::Int(3)).unwrap();
+
+        fs::write(slot_path(&directory, 2), "version: 99\nvalues: {}").unwrap();
+        assert!(load_slot(2).is_err());
+        assert_eq!(slot(), 0);
+        assert_eq!(format!("{:?}", load_value("coins")), format!("{:?}", Some(Token::Int(3))));
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
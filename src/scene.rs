@@ -1,7 +1,15 @@
-use crate::object::TObject;
+use crate::log_warn;
+use crate::object::{TObject, Object2D, Object3D, Camera2D, Camera3D};
+use raylib::math::Vector2;
+use crate::transform::{Transform2D, Transform3D};
 use crate::scene_manager::scene::script_manager_mod::ScriptManager;
 
-use yaml_rust::Yaml;
+use yaml_rust::{Yaml, YamlEmitter};
+use yaml_rust::yaml::Hash;
+use std::fs;
+use crate::scene_manager::object_registry::ObjectRegistry;
+use drython::types::Token;
+use crate::input;
 
 #[path="script_manager.rs"]
 mod script_manager_mod;
@@ -11,7 +19,18 @@ pub struct Scene
     pub scene_path: String,
     pub loaded_scene: Yaml,
     pub objects: Vec<Box<dyn TObject>>,
-    pub script_manager: script_manager_mod::ScriptManager
+    pub script_manager: script_manager_mod::ScriptManager,
+
+    // Overlays such as a HUD do not cover the scenes below them.
+    pub overlay: bool,
+    pub update_when_covered: bool,
+    pub draw_when_covered: bool,
+
+    // Render layers from the bottom up.
+    pub render_layers: Vec<String>,
+
+    // Types the scene was loaded with, used to save it.
+    pub object_types: ObjectRegistry,
 }
 
 impl Scene
@@ -24,11 +43,325 @@ impl Scene
             loaded_scene: Yaml::BadValue,
             objects: vec![],
             script_manager: ScriptManager::new(),
+
+            overlay: false,
+            update_when_covered: false,
+            draw_when_covered: true,
+
+            render_layers: vec![],
+
+            object_types: ObjectRegistry::new(),
+        }
+    }
+
+    // Sets up the scripts and runs their start callback.
+    pub fn start(&mut self)
+    {
+        self.script_manager.run_setup(&mut self.objects);
+        self.script_manager.run_function_all(&mut self.objects, "start", None);
+        self.script_manager.update_variables(&mut self.objects);
+    }
+
+    // Sets up and starts the scripts of objects added after the scene started.
+    pub fn start_objects(&mut self, ids: &[usize])
+    {
+        for id in ids
+        {
+            self.script_manager.setup_script(&mut self.objects, *id);
+        }
+        for id in ids
+        {
+            self.script_manager.run_function(&mut self.objects, *id, "start", vec![]);
+        }
+        self.script_manager.update_variables(&mut self.objects);
+    }
+
+    pub fn find_object(&self, id: usize) -> Option<&Box<dyn TObject>>
+    {
+        self.objects.iter().find(|object| object.get_id() == id)
+    }
+
+    // The transform of a 2d object after applying all of its parents' transforms.
+    // Positions are interpolated between the last two fixed updates by alpha.
+    pub fn world_transform_2d(&self, id: usize, alpha: f32) -> Option<Transform2D>
+    {
+        let object2d = self.find_object(id)?.downcast_ref::<Object2D>()?;
+
+        let mut local = object2d.transform;
+        local.pos = object2d.previous_pos.lerp(object2d.transform.pos, alpha);
+
+        match object2d.object.parent.and_then(|parent| self.world_transform_2d(parent, alpha))
+        {
+            Some(parent) => Some(parent.combine(&local)),
+            None => Some(local),
+        }
+    }
+
+    // Objects without a layer, or with one the scene does not declare, go in the bottom layer.
+    pub fn layer_index(&self, layer: &str) -> usize
+    {
+        self.render_layers.iter().position(|x| x == layer).unwrap_or(0)
+    }
+
+    // 2d objects in the order they should be drawn. Objects with the same layer and
+    // z index keep the order they were added in.
+    pub fn draw_order_2d(&self) -> Vec<&Object2D>
+    {
+        let mut ordered: Vec<&Object2D> = self.objects.iter()
+            .filter_map(|object| object.downcast_ref::<Object2D>())
+            .collect();
+
+        ordered.sort_by_key(|object2d| (self.layer_index(&object2d.layer), object2d.z_index));
+
+        ordered
+    }
+
+    // The transform of a 3d object after applying all of its parents' transforms.
+    pub fn world_transform_3d(&self, id: usize) -> Option<Transform3D>
+    {
+        let object3d = self.find_object(id)?.downcast_ref::<Object3D>()?;
+
+        match object3d.object.parent.and_then(|parent| self.world_transform_3d(parent))
+        {
+            Some(parent) => Some(parent.combine(&object3d.transform)),
+            None => Some(object3d.transform),
+        }
+    }
+
+    pub fn active_camera_3d(&self) -> Option<&Camera3D>
+    {
+        self.objects.iter()
+            .filter_map(|object| object.downcast_ref::<Camera3D>())
+            .find(|camera| camera.active)
+    }
+
+    pub fn active_camera_2d(&self) -> Option<&Camera2D>
+    {
+        self.objects.iter()
+            .filter_map(|object| object.downcast_ref::<Camera2D>())
+            .find(|camera| camera.active)
+    }
+
+    // Moves cameras after their followed objects and starts any requested shakes
+    // on the active camera. Their scripts are given the new target, which they
+    // would otherwise write back over it.
+    pub fn update_cameras(&mut self, delta: f32, shakes: &[(f32, f32)])
+    {
+        let follow_positions: Vec<Option<Vector2>> = self.objects.iter()
+            .map(|object|
+            {
+                let camera = object.downcast_ref::<Camera2D>()?;
+                let followed = self.objects.iter().find(|x| !camera.follow.is_empty() && x.get_name() == camera.follow)?;
+                self.world_transform_2d(followed.get_id(), 1.0).map(|world| world.pos)
+            })
+            .collect();
+
+        let mut shaken = false;
+        for (object, follow_pos) in self.objects.iter_mut().zip(follow_positions)
+        {
+            if let Some(camera) = object.downcast_mut::<Camera2D>()
+            {
+                if camera.active && !shaken
+                {
+                    for (intensity, duration) in shakes
+                    {
+                        camera.shake(*intensity, *duration);
+                    }
+                    shaken = true;
+                }
+
+                camera.update(delta, follow_pos);
+                self.script_manager.share_variables(object);
+            }
+        }
+    }
+
+    // Calls the functions objects registered for actions that were pressed or released.
+    pub fn dispatch_input_actions(&mut self)
+    {
+        let mut calls = vec![];
+        for object in &self.objects
+        {
+            for (action, function) in &object.get_obj_ref().inputs
+            {
+                if input::is_action_pressed(action)
+                {
+                    calls.push((object.get_id(), function.clone(), true));
+                }
+                else if input::is_action_released(action)
+                {
+                    calls.push((object.get_id(), function.clone(), false));
+                }
+            }
+        }
+
+        for (id, function, pressed) in calls
+        {
+            self.script_manager.run_function(&mut self.objects, id, &function, vec![Token::Bool(pressed)]);
+        }
+    }
+
+    // Writes the current state of the scene in the format it is loaded from.
+    // Keys the engine does not use are kept from the loaded yaml.
+    pub fn save(&self, path: &str) -> Result<(), String>
+    {
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&self.to_yaml()).map_err(|error| format!("{:?}", error))?;
+        fs::write(path, out).map_err(|error| error.to_string())
+    }
+
+    pub fn to_yaml(&self) -> Yaml
+    {
+        let mut doc = self.loaded_scene.as_hash().cloned().unwrap_or_default();
+        for old_key in ["objects", "objects 2d", "cameras 2d"]
+        {
+            doc.remove(&Yaml::String(old_key.to_string()));
+        }
+
+        doc.insert(Yaml::String("update_when_covered".to_string()), Yaml::Boolean(self.update_when_covered));
+        doc.insert(Yaml::String("draw_when_covered".to_string()), Yaml::Boolean(self.draw_when_covered));
+        doc.insert(Yaml::String("layers".to_string()),
+            Yaml::Array(self.render_layers.iter().map(|layer| Yaml::String(layer.clone())).collect()));
+
+        let objects = self.objects.iter()
+            .filter(|object| object.get_obj_ref().parent.is_none())
+            .filter_map(|object| self.object_to_yaml(object.as_ref()))
+            .collect();
+        doc.insert(Yaml::String("objects".to_string()), Yaml::Array(objects));
+
+        Yaml::Hash(doc)
+    }
+
+    fn object_to_yaml(&self, object: &dyn TObject) -> Option<Yaml>
+    {
+        let mut hash = self.object_properties(object)?;
+
+        let children: Vec<Yaml> = object.get_obj_ref().children.iter()
+            .filter_map(|id| self.find_object(*id))
+            .filter_map(|child| self.object_to_yaml(child.as_ref()))
+            .collect();
+        if children.is_empty()
+        {
+            hash.remove(&Yaml::String("children".to_string()));
+        }
+        else
+        {
+            hash.insert(Yaml::String("children".to_string()), Yaml::Array(children));
+        }
+
+        Some(Yaml::Hash(hash))
+    }
+
+    // The object's current properties, leaving its children as they were loaded.
+    pub fn object_properties(&self, object: &dyn TObject) -> Option<Hash>
+    {
+        let obj = object.get_obj_ref();
+        let saver = match self.object_types.get_saver(&obj.type_name)
+        {
+            Some(saver) => saver,
+            None =>
+            {
+                log_warn!("scene", "Object {} has no saver for its type {:?}, it will not be saved.", obj.name, obj.type_name);
+                return None;
+            }
+        };
+
+        let mut hash = obj.loaded_properties.as_hash().cloned().unwrap_or_default();
+        hash.insert(Yaml::String("type".to_string()), Yaml::String(obj.type_name.clone()));
+        hash.insert(Yaml::String("name".to_string()), Yaml::String(obj.name.clone()));
+        match obj.scripts.as_slice()
+        {
+            [] => (),
+            [script] => { hash.insert(Yaml::String("script".to_string()), Yaml::String(script.clone())); }
+            scripts =>
+            {
+                hash.insert(Yaml::String("script".to_string()),
+                    Yaml::Array(scripts.iter().map(|script| Yaml::String(script.clone())).collect()));
+            }
+        }
+
+        saver(object, &mut hash);
+
+        Some(hash)
+    }
+
+    // The first object without a parent that has the name.
+    pub fn find_top_level(&self, name: &str) -> Option<usize>
+    {
+        self.objects.iter()
+            .find(|object| object.get_obj_ref().parent.is_none() && object.get_obj_ref().name == name)
+            .map(|object| object.get_id())
+    }
+
+    pub fn reload_scripts(&mut self)
+    {
+        self.script_manager.reload_changed_scripts(&mut self.objects);
+    }
+
+    // Sets one of the variables an object exposes to scripts, and its scripts' copies of it.
+    pub fn set_object_var(&mut self, id: usize, name: &str, value: &Token)
+    {
+        if let Some(object) = self.objects.iter_mut().find(|object| object.get_id() == id)
+        {
+            if let Err(error) = object.set_drython_var(name, value)
+            {
+                log_warn!("scene", "Could not set a variable of {}: {}.", object.get_name(), error);
+                return;
+            }
+
+            self.script_manager.share_variables(object);
+        }
+    }
+
+    // Calls a function in each of an object's scripts, then applies any changes it made to the object.
+    pub fn call_object(&mut self, id: usize, function: &str, args: Vec<Token>)
+    {
+        self.script_manager.run_function(&mut self.objects, id, function, args);
+        self.script_manager.update_variables(&mut self.objects);
+    }
+
+    // Calls on_destroy for the object and its children, then drops them along with
+    // their scripts, which frees their textures and runners.
+    pub fn destroy(&mut self, id: usize)
+    {
+        let mut removed = vec![id];
+        let mut index = 0;
+        while index < removed.len()
+        {
+            if let Some(object) = self.find_object(removed[index])
+            {
+                removed.extend(object.get_obj_ref().children.iter().copied());
+            }
+            index += 1;
+        }
+
+        for id in &removed
+        {
+            self.script_manager.run_function(&mut self.objects, *id, "on_destroy", vec![]);
         }
+
+        let parent = self.find_object(id).and_then(|object| object.get_obj_ref().parent);
+        if let Some(parent) = self.objects.iter_mut().find(|object| Some(object.get_id()) == parent)
+        {
+            parent.get_obj().children.retain(|child| *child != id);
+        }
+
+        for id in &removed
+        {
+            self.script_manager.scripts.remove(id);
+        }
+        self.objects.retain(|object| !removed.contains(&object.get_id()));
+
+        // Removing objects moves the rest, so every object's externals are registered again.
+        self.script_manager.register_externals(&mut self.objects);
     }
 
+    // Runs the exit callback then drops the scripts and objects, which frees their textures.
     pub fn unload(&mut self)
     {
-        
+        self.script_manager.run_function_all(&mut self.objects, "exit", None);
+        self.script_manager.scripts.clear();
+        self.objects.clear();
+        self.loaded_scene = Yaml::BadValue;
     }
 }
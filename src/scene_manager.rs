@@ -1,6 +1,9 @@
-use crate::object::TObject;
+use crate::{log_error, log_info, log_warn};
+use crate::object::{TObject, Object2D, ObjectRequest};
 #[path="scene.rs"]
-mod scene;
+pub mod scene;
+#[path="object_registry.rs"]
+pub mod object_registry;
 
 use yaml_rust::Yaml;
 use yaml_rust::YamlLoader;
@@ -8,12 +11,199 @@ use crate::Raylib;
 use std::fs;
 
 use scene::Scene;
-use crate::object::Object2D;
-use raylib::prelude::Vector2;
+use object_registry::{ObjectRegistry, texture_path, reload_texture};
+use raylib::prelude::{Vector2, Rectangle, Color};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::SystemTime;
 
+// Yaml numbers can be written as integers or reals.
+pub fn read_f32(value: &Yaml) -> Option<f32>
+{
+    match value
+    {
+        Yaml::Integer(i) => Some(*i as f32),
+        Yaml::Real(_) => value.as_f64().map(|f| f as f32),
+        _ => None
+    }
+}
+
+// Reads {x, y}, missing components use the default.
+pub fn read_vector2(value: &Yaml, default: Vector2) -> Vector2
+{
+    Vector2::new(read_f32(&value["x"]).unwrap_or(default.x), read_f32(&value["y"]).unwrap_or(default.y))
+}
+
+// Reads {r, g, b, a} or [r, g, b, a] from 0 to 255, alpha defaults to 255.
+pub fn read_color(value: &Yaml) -> Color
+{
+    let channel = |key: &str, index: usize, default: f32|
+    {
+        let channel = if value.is_array() { &value[index] } else { &value[key] };
+        read_f32(channel).unwrap_or(default).clamp(0.0, 255.0) as u8
+    };
+
+    Color::new(channel("r", 0, 255.0), channel("g", 1, 255.0), channel("b", 2, 255.0), channel("a", 3, 255.0))
+}
+
+// Reads {x, y, width, height}.
+pub fn read_rectangle(value: &Yaml) -> Rectangle
+{
+    Rectangle::new(
+        read_f32(&value["x"]).unwrap_or(0.0),
+        read_f32(&value["y"]).unwrap_or(0.0),
+        read_f32(&value["width"]).unwrap_or(0.0),
+        read_f32(&value["height"]).unwrap_or(0.0))
+}
+
+// Scene changes requested by scripts, applied by the game between frames.
+pub enum SceneRequest
+{
+    Change(String),
+    Push(String),
+    Overlay(String),
+    Pop,
+}
+
+thread_local!
+{
+    static SCENE_REQUESTS: RefCell<Vec<SceneRequest>> = RefCell::new(vec![]);
+}
+
+pub fn request_scene(request: SceneRequest)
+{
+    SCENE_REQUESTS.with(|requests| requests.borrow_mut().push(request));
+}
+
+// Requests in the order they were made.
+pub fn take_scene_requests() -> Vec<SceneRequest>
+{
+    SCENE_REQUESTS.with(|requests| requests.borrow_mut().drain(..).collect())
+}
+
+// Objects requested by scripts, given as the same yaml a scene lists them with, along with
+// the id of the object that asked. They are added to its scene once the update finishes.
+thread_local!
+{
+    static SPAWN_REQUESTS: RefCell<Vec<(usize, Yaml)>> = RefCell::new(vec![]);
+}
+
+pub fn request_spawn(requester: usize, properties: Yaml)
+{
+    SPAWN_REQUESTS.with(|requests| requests.borrow_mut().push((requester, properties)));
+}
+
+pub fn take_spawn_requests() -> Vec<(usize, Yaml)>
+{
+    SPAWN_REQUESTS.with(|requests| requests.borrow_mut().drain(..).collect())
+}
+
+// Ids of objects scripts asked to remove, removed once the update finishes.
+thread_local!
+{
+    static DESTROY_REQUESTS: RefCell<Vec<usize>> = RefCell::new(vec![]);
+}
+
+pub fn request_destroy(id: usize)
+{
+    DESTROY_REQUESTS.with(|requests| requests.borrow_mut().push(id));
+}
+
+pub fn take_destroy_requests() -> Vec<usize>
+{
+    DESTROY_REQUESTS.with(|requests| requests.borrow_mut().drain(..).collect())
+}
+
+// Tags and scripts can be a single string or a list of them.
+fn read_list(value: &Yaml) -> Vec<String>
+{
+    match value
+    {
+        Yaml::String(tag) => vec![tag.clone()],
+        Yaml::Array(tags) => tags.iter().filter_map(|tag| tag.as_str()).map(|tag| tag.to_string()).collect(),
+        _ => vec![],
+    }
+}
+
+// Reads a prefab file from the assets folder.
+fn read_prefab(assets: &str, prefab_path: &str) -> Option<Yaml>
+{
+    let file = match fs::read_to_string(format!("{}/{}", assets, prefab_path))
+    {
+        Ok(file) => file,
+        Err(_) =>
+        {
+            log_error!("scene", "Failed to find prefab file: {}", prefab_path);
+            return None;
+        }
+    };
+
+    match YamlLoader::load_from_str(&file)
+    {
+        Ok(mut yaml) if !yaml.is_empty() => Some(yaml.remove(0)),
+        Ok(_) => { log_error!("scene", "Failed to load prefab file: {} due to invalid format.", prefab_path); None }
+        Err(error) => { log_error!("scene", "Failed to load prefab file: {} due to error: {}.", prefab_path, error); None }
+    }
+}
+
+// An object with a prefab key starts from the prefab's properties, which any other
+// keys given alongside it replace. Prefabs can be based on other prefabs.
+pub fn apply_prefab(unloaded: &Yaml) -> Yaml
+{
+    resolve_prefab("assets", unloaded, &mut vec![])
+}
+
+// Visited holds the prefabs being resolved, so one that is based on itself, directly or
+// through others, is reported instead of being read forever. It is left out like a
+// prefab that fails to load.
+fn resolve_prefab(assets: &str, unloaded: &Yaml, visited: &mut Vec<String>) -> Yaml
+{
+    let prefab_path = match unloaded["prefab"].as_str()
+    {
+        Some(prefab_path) => prefab_path,
+        None => return unloaded.clone(),
+    };
+
+    if visited.iter().any(|path| path == prefab_path)
+    {
+        log_error!("scene", "Failed to load prefab file: {} as it is based on itself through {}.", prefab_path, visited.join(" -> "));
+        return unloaded.clone();
+    }
+
+    visited.push(prefab_path.to_string());
+    let prefab = read_prefab(assets, prefab_path).map(|prefab| resolve_prefab(assets, &prefab, visited));
+    visited.pop();
+
+    let mut properties = match prefab
+    {
+        Some(Yaml::Hash(prefab)) => prefab,
+        _ => return unloaded.clone(),
+    };
+
+    properties.remove(&Yaml::String("prefab".to_string()));
+    if let Some(overrides) = unloaded.as_hash()
+    {
+        for (key, value) in overrides
+        {
+            if key.as_str() != Some("prefab")
+            {
+                properties.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    Yaml::Hash(properties)
+}
+
+// Scenes are kept as a stack, the last scene is on top.
+// Updating and drawing both go from the bottom of the stack to the top.
 pub struct SceneManager
 {
-    pub current_scene: Option<Scene>
+    pub scenes: Vec<Scene>,
+    pub object_types: ObjectRegistry,
+
+    // When each scene and texture file was last changed, to know when to reload it.
+    modified: HashMap<String, SystemTime>,
 }
 
 impl SceneManager
@@ -22,18 +212,403 @@ impl SceneManager
     {
         SceneManager
         {
-            current_scene: None,
+            scenes: vec![],
+            object_types: ObjectRegistry::new(),
+            modified: HashMap::new(),
         }
     }
 
-    pub fn load(& mut self, raylib: &mut Raylib, scene_path: &str)
+    // Replaces every loaded scene with the given one.
+    pub fn load(& mut self, raylib: Option<&mut Raylib>, scene_path: &str)
     {
-        if let Some(scene) = &mut self.current_scene
+        self.unload_all();
+
+        self.push(raylib, scene_path);
+    }
+
+    // Loads a scene on top of the stack, covering the scenes below it.
+    pub fn push(&mut self, raylib: Option<&mut Raylib>, scene_path: &str)
+    {
+        let new_scene = SceneManager::read_scene(raylib, &self.object_types, scene_path);
+        self.file_changed(scene_path);
+        self.scenes.push(new_scene);
+    }
+
+    // Loads a scene on top of the stack without covering the scenes below, such as a HUD.
+    pub fn overlay(&mut self, raylib: Option<&mut Raylib>, scene_path: &str)
+    {
+        let mut new_scene = SceneManager::read_scene(raylib, &self.object_types, scene_path);
+        new_scene.overlay = true;
+        self.file_changed(scene_path);
+        self.scenes.push(new_scene);
+    }
+
+    // Unloads the top scene.
+    pub fn pop(&mut self)
+    {
+        if let Some(mut scene) = self.scenes.pop()
         {
             scene.unload();
         }
+    }
+
+    pub fn unload_all(&mut self)
+    {
+        while !self.scenes.is_empty()
+        {
+            self.pop();
+        }
+    }
+
+    pub fn current_scene(&self) -> Option<&Scene>
+    {
+        self.scenes.last()
+    }
+
+    pub fn current_scene_mut(&mut self) -> Option<&mut Scene>
+    {
+        self.scenes.last_mut()
+    }
+
+    // A scene is covered when any non overlay scene is above it.
+    pub fn is_covered(&self, index: usize) -> bool
+    {
+        self.scenes.iter().skip(index + 1).any(|scene| !scene.overlay)
+    }
+
+    pub fn is_updating(&self, index: usize) -> bool
+    {
+        !self.is_covered(index) || self.scenes[index].update_when_covered
+    }
+
+    pub fn updating_scenes(&mut self) -> Vec<&mut Scene>
+    {
+        let updating: Vec<bool> = (0..self.scenes.len()).map(|i| self.is_updating(i)).collect();
+
+        self.scenes.iter_mut().zip(updating)
+            .filter(|(_, updating)| *updating)
+            .map(|(scene, _)| scene)
+            .collect()
+    }
+
+    pub fn drawing_scenes(&self) -> Vec<&Scene>
+    {
+        self.drawing_scenes_updating().into_iter().map(|(scene, _)| scene).collect()
+    }
 
+    // Scenes to draw along with whether they are being updated. Scenes that are not
+    // updated are drawn where their objects are, as there is nothing to interpolate.
+    pub fn drawing_scenes_updating(&self) -> Vec<(&Scene, bool)>
+    {
+        self.scenes.iter().enumerate()
+            .filter(|(i, scene)| !self.is_covered(*i) || scene.draw_when_covered)
+            .map(|(i, scene)| (scene, self.is_updating(i)))
+            .collect()
+    }
+
+    // Index of the scene the object is in.
+    pub fn scene_of(&self, id: usize) -> Option<usize>
+    {
+        self.scenes.iter().position(|scene| scene.find_object(id).is_some())
+    }
+
+    // Adds an object to a scene while it is running, and starts its script along with
+    // those of its children.
+    pub fn spawn(&mut self, raylib: Option<&mut Raylib>, scene_index: usize, properties: &Yaml) -> Option<usize>
+    {
+        let scene = self.scenes.get_mut(scene_index)?;
+        let first_new = scene.objects.len();
+        let id = SceneManager::create_object(raylib, &self.object_types, scene, "", properties, "object2d", None)?;
+
+        // Adding objects can move the others, so every object's externals are registered again.
+        scene.script_manager.register_externals(&mut scene.objects);
+
+        let new_ids: Vec<usize> = scene.objects[first_new..].iter().map(|object| object.get_id()).collect();
+        scene.start_objects(&new_ids);
+
+        Some(id)
+    }
+
+    // Sets a variable on, or calls a function of, an object in whichever scene has it.
+    pub fn apply_object_request(&mut self, request: ObjectRequest)
+    {
+        let id = match &request
+        {
+            ObjectRequest::SetVar(id, _, _) | ObjectRequest::Call(id, _, _) => *id,
+        };
+
+        let scene = match self.scenes.iter_mut().find(|scene| scene.find_object(id).is_some())
+        {
+            Some(scene) => scene,
+            // Destroyed before the request was applied.
+            None => return,
+        };
+
+        match request
+        {
+            ObjectRequest::SetVar(id, name, value) => scene.set_object_var(id, &name, &value),
+            ObjectRequest::Call(id, function, args) => scene.call_object(id, &function, args),
+        }
+    }
+
+    // Removes an object, and its children, from whichever scene has it.
+    pub fn destroy(&mut self, id: usize) -> bool
+    {
+        match self.scenes.iter_mut().find(|scene| scene.find_object(id).is_some())
+        {
+            Some(scene) => { scene.destroy(id); true }
+            None => false,
+        }
+    }
+
+    // True when the file changed since it was last checked. The first check only
+    // remembers the time.
+    fn file_changed(&mut self, path: &str) -> bool
+    {
+        let modified = match fs::metadata(path).and_then(|metadata| metadata.modified())
+        {
+            Ok(modified) => modified,
+            Err(_) => return false,
+        };
+
+        match self.modified.insert(path.to_string(), modified)
+        {
+            Some(previous) => previous != modified,
+            None => false,
+        }
+    }
+
+    // Reloads changed textures in place, and applies changes made to scene files
+    // to the loaded scenes.
+    pub fn reload_changed_assets(&mut self, mut raylib: Option<&mut Raylib>)
+    {
+        let mut texture_paths: Vec<String> = self.scenes.iter()
+            .flat_map(|scene| scene.objects.iter())
+            .filter_map(|object| texture_path(object.as_ref()).map(|path| path.to_string()))
+            .collect();
+        texture_paths.sort();
+        texture_paths.dedup();
+
+        let changed: Vec<String> = texture_paths.into_iter()
+            .filter(|path| self.file_changed(&format!("assets/{}", path)))
+            .collect();
+
+        for object in self.scenes.iter_mut().flat_map(|scene| scene.objects.iter_mut())
+        {
+            if texture_path(object.as_ref()).map_or(false, |path| changed.iter().any(|changed| changed == path))
+            {
+                reload_texture(object.as_mut(), raylib.as_deref_mut());
+            }
+        }
+
+        for index in 0..self.scenes.len()
+        {
+            let scene_path = self.scenes[index].scene_path.clone();
+            if self.file_changed(&scene_path)
+            {
+                self.reload_scene(raylib.as_deref_mut(), index);
+            }
+        }
+    }
+
+    // Objects of a scene file by name, with the type they default to. Unnamed objects
+    // can not be matched up, so they are left out.
+    fn named_entries(unloaded: &Yaml) -> Vec<(String, Yaml, &'static str)>
+    {
+        let mut named = vec![];
+        for (section, default_type) in [("objects", ""), ("objects 2d", "object2d"), ("cameras 2d", "camera")]
+        {
+            for (name, object) in SceneManager::entries(&unloaded[section])
+            {
+                let name = object["name"].as_str().unwrap_or(name);
+                if !name.is_empty()
+                {
+                    named.push((name.to_string(), object.clone(), default_type));
+                }
+            }
+        }
+
+        named
+    }
+
+    // Compares the scene file with the version the scene was loaded from. Objects that are
+    // still there keep their runtime state, with only the properties that changed in the
+    // file applied. Removed objects are destroyed and new ones spawned.
+    fn reload_scene(&mut self, mut raylib: Option<&mut Raylib>, index: usize)
+    {
+        let scene = &mut self.scenes[index];
+        let loaded = fs::read_to_string(&scene.scene_path).map_err(|error| error.to_string())
+            .and_then(|file| YamlLoader::load_from_str(&file).map_err(|error| error.to_string()));
+        let unloaded = match loaded
+        {
+            Ok(mut yaml) if !yaml.is_empty() => yaml.remove(0),
+            Ok(_) =>
+            {
+                log_warn!("scene", "Scene {} changed but is empty, the loaded version is kept.", scene.scene_path);
+                return;
+            }
+            Err(error) =>
+            {
+                log_warn!("scene", "Scene {} changed but failed to load due to {}, the loaded version is kept.", scene.scene_path, error);
+                return;
+            }
+        };
+
+        let old_entries = SceneManager::named_entries(&scene.loaded_scene);
+        let new_entries = SceneManager::named_entries(&unloaded);
+
+        for (name, _, _) in &old_entries
+        {
+            if !new_entries.iter().any(|entry| &entry.0 == name)
+            {
+                if let Some(id) = scene.find_top_level(name)
+                {
+                    scene.destroy(id);
+                }
+            }
+        }
+
+        let mut started = vec![];
+        for (name, entry, default_type) in &new_entries
+        {
+            let old_entry = old_entries.iter().find(|old| &old.0 == name).map(|old| &old.1);
+            if old_entry == Some(entry)
+            {
+                continue;
+            }
+
+            match (old_entry, scene.find_top_level(name))
+            {
+                (Some(old_entry), Some(id)) =>
+                {
+                    started.extend(SceneManager::update_object(raylib.as_deref_mut(), &self.object_types, scene, id, old_entry, entry, default_type));
+                }
+                _ =>
+                {
+                    let first_new = scene.objects.len();
+                    SceneManager::create_live_object(raylib.as_deref_mut(), &self.object_types, scene, name, entry, default_type, None);
+                    started.extend(scene.objects[first_new..].iter().map(|object| object.get_id()));
+                }
+            }
+        }
+
+        scene.update_when_covered = unloaded["update_when_covered"].as_bool().unwrap_or(false);
+        scene.draw_when_covered = unloaded["draw_when_covered"].as_bool().unwrap_or(true);
+        scene.render_layers = match &unloaded["layers"]
+        {
+            Yaml::Array(layers) => layers.iter().filter_map(|layer| layer.as_str()).map(|layer| layer.to_string()).collect(),
+            _ => vec![],
+        };
+        scene.loaded_scene = unloaded;
+
+        scene.script_manager.register_externals(&mut scene.objects);
+        scene.start_objects(&started);
+
+        log_info!("scene", "Reloaded scene {}.", scene.scene_path);
+    }
+
+    // Applies the properties that changed between two versions of an object's entry to the
+    // live object. It is rebuilt from its current properties with the changes on top, and
+    // keeps its id, script and runtime state. Returns the ids of scripts that need starting.
+    fn update_object(mut raylib: Option<&mut Raylib>, object_types: &ObjectRegistry, scene: &mut Scene,
+                     id: usize, old_entry: &Yaml, entry: &Yaml, default_type: &str) -> Vec<usize>
+    {
+        let old = apply_prefab(old_entry);
+        let new = apply_prefab(entry);
+        let type_name = new["type"].as_str().unwrap_or(default_type);
+        let position = match scene.objects.iter().position(|object| object.get_id() == id)
+        {
+            Some(position) => position,
+            None => return vec![],
+        };
+
+        // A different type is a different object.
+        if scene.objects[position].get_obj_ref().type_name != type_name
+        {
+            let name = scene.objects[position].get_name();
+            scene.destroy(id);
+            let first_new = scene.objects.len();
+            SceneManager::create_live_object(raylib.as_deref_mut(), object_types, scene, &name, entry, default_type, None);
+            return scene.objects[first_new..].iter().map(|object| object.get_id()).collect();
+        }
+
+        let changed: Vec<(Yaml, Yaml)> = new.as_hash().map(|hash| hash.iter()
+            .filter(|(key, value)| old.as_hash().and_then(|old| old.get(*key)) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()).unwrap_or_default();
+        let is_changed = |key: &str| changed.iter().any(|(changed_key, _)| changed_key.as_str() == Some(key));
+
+        let loader = match object_types.get(type_name)
+        {
+            Some(loader) => loader,
+            None => return vec![],
+        };
+        let mut properties = scene.object_properties(scene.objects[position].as_ref()).unwrap_or_default();
+        properties.remove(&Yaml::String("children".to_string()));
+        for (key, value) in &changed
+        {
+            properties.insert(key.clone(), value.clone());
+        }
+
+        let mut new_obj = loader(raylib.as_deref_mut(), scene, &Yaml::Hash(properties));
+        if let Some(object2d) = new_obj.downcast_mut::<Object2D>()
+        {
+            object2d.reset_interpolation();
+        }
+        std::mem::swap(new_obj.get_obj(), scene.objects[position].get_obj());
+        new_obj.get_obj().loaded_properties = entry.clone();
+        if is_changed("tags")
+        {
+            new_obj.get_obj().tags = read_list(&new["tags"]);
+        }
+        if is_changed("group")
+        {
+            new_obj.get_obj().group = new["group"].as_str().unwrap_or("").to_string();
+        }
+
+        let mut started = vec![];
+        if is_changed("script")
+        {
+            new_obj.get_obj().scripts = read_list(&new["script"]);
+            scene.script_manager.scripts.remove(&id);
+            if !new["script"].is_badvalue()
+            {
+                scene.script_manager.handle_script(new_obj.get_obj_ref(), &new["script"]);
+                started.push(id);
+            }
+        }
+        scene.objects[position] = new_obj;
+
+        // Children are made again from the file when any of them changed.
+        if is_changed("children")
+        {
+            let children = scene.objects[position].get_obj_ref().children.clone();
+            for child in children
+            {
+                scene.destroy(child);
+            }
+
+            for (name, child) in SceneManager::entries(&new["children"])
+            {
+                let first_new = scene.objects.len();
+                if let Some(child_id) = SceneManager::create_live_object(raylib.as_deref_mut(), object_types, scene, name, child, type_name, Some(id))
+                {
+                    started.extend(scene.objects[first_new..].iter().map(|object| object.get_id()));
+                    if let Some(parent) = scene.objects.iter_mut().find(|object| object.get_id() == id)
+                    {
+                        parent.get_obj().children.push(child_id);
+                    }
+                }
+            }
+        }
+
+        started
+    }
+
+    fn read_scene(raylib: Option<&mut Raylib>, object_types: &ObjectRegistry, scene_path: &str) -> Scene
+    {
         let mut new_scene = Scene::new(scene_path.to_string());
+        new_scene.object_types = object_types.clone();
 
         match fs::read_to_string(scene_path)
         {
@@ -47,77 +622,430 @@ impl SceneManager
                         // values are not present.
                         if let Some(yaml_doc) = yaml.pop()
                         {
-                            SceneManager::initialize_scene(raylib, &mut new_scene, &yaml_doc);
+                            SceneManager::initialize_scene(raylib, object_types, &mut new_scene, &yaml_doc);
                             new_scene.loaded_scene = yaml_doc;
                         }
                         else
                         {
-                            println!("Failed to load yaml file: {} due to invalid format.", scene_path);
+                            log_error!("scene", "Failed to load yaml file: {} due to invalid format.", scene_path);
                         }
                     }
                     Err(error) =>
                     {
-                        println!("Failed to load yaml file: {} due to error: {}.", scene_path, error);
+                        log_error!("scene", "Failed to load yaml file: {} due to error: {}.", scene_path, error);
                     }
                 }
             }
             Err(_) =>
             {
-                println!("Failed to find startup file: {}", scene_path);
+                log_error!("scene", "Failed to find startup file: {}", scene_path);
             }
         }
 
-        self.current_scene = Some(new_scene);
+        new_scene
     }
 
-    fn initialize_scene(raylib: &mut Raylib, scene: &mut Scene, unloaded: &Yaml)
+    fn initialize_scene(mut raylib: Option<&mut Raylib>, object_types: &ObjectRegistry, scene: &mut Scene, unloaded: &Yaml)
     {
-        if let Yaml::Hash(hash) = &unloaded["objects 2d"]
+        if let Some(update_when_covered) = unloaded["update_when_covered"].as_bool()
         {
-            for object in hash
-            {
-                let mut new_obj = Object2D::new();
+            scene.update_when_covered = update_when_covered;
+        }
+        if let Some(draw_when_covered) = unloaded["draw_when_covered"].as_bool()
+        {
+            scene.draw_when_covered = draw_when_covered;
+        }
 
-                if let Yaml::Hash(params) = object.1
-                {
-                    for param in params
-                    {
-                        if let Some(param_name) = param.0.as_str()
-                        {
-                            match param_name
-                            {
-                                "name" => { new_obj.object.name = param.1.as_str().unwrap_or("").to_string(); }
-                                "sprite" => SceneManager::handle_sprite(&mut new_obj, raylib, param.1),
-                                "pos" => { new_obj.transform.pos =
-                                    Vector2::new(param.1["x"].as_f64().unwrap_or(0.0) as f32, param.1["y"].as_f64().unwrap_or(0.0) as f32); },
-                                "script" => scene.script_manager.handle_script(&new_obj.object, param.1),
-                                _ => ()
-                            }
-                        }
-                    }
+        if let Yaml::Array(layers) = &unloaded["layers"]
+        {
+            scene.render_layers = layers.iter().filter_map(|layer| layer.as_str()).map(|layer| layer.to_string()).collect();
+        }
 
-                }
+        // Objects of any type, each with a type key.
+        for (name, object) in SceneManager::entries(&unloaded["objects"])
+        {
+            SceneManager::create_object(raylib.as_deref_mut(), object_types, scene, name, object, "", None);
+        }
 
-                scene.objects.push(Box::new(new_obj));
-            }
+        // Older scenes list their types separately.
+        for (name, object) in SceneManager::entries(&unloaded["objects 2d"])
+        {
+            SceneManager::create_object(raylib.as_deref_mut(), object_types, scene, name, object, "object2d", None);
+        }
+        for (name, camera) in SceneManager::entries(&unloaded["cameras 2d"])
+        {
+            SceneManager::create_object(raylib.as_deref_mut(), object_types, scene, name, camera, "camera", None);
+        }
+
+        // Register any script vars.
+        scene.script_manager.register_externals(&mut scene.objects);
+    }
+
+    // Creates an object in a scene whose scripts are already running. Adding it can move the
+    // other objects, so their externals are registered again before any script runs.
+    fn create_live_object(raylib: Option<&mut Raylib>, object_types: &ObjectRegistry, scene: &mut Scene,
+                          default_name: &str, entry: &Yaml, default_type: &str, parent: Option<usize>) -> Option<usize>
+    {
+        let id = SceneManager::create_object(raylib, object_types, scene, default_name, entry, default_type, parent);
+        scene.script_manager.register_externals(&mut scene.objects);
+        id
+    }
 
-            // Register any script vars.
-            scene.script_manager.register_externals(&mut scene.objects);
+    // Objects can be listed as a hash, where the key is the default name, or as an array.
+    fn entries(objects: &Yaml) -> Vec<(&str, &Yaml)>
+    {
+        match objects
+        {
+            Yaml::Hash(hash) => hash.iter().map(|object| (object.0.as_str().unwrap_or(""), object.1)).collect(),
+            Yaml::Array(array) => array.iter().map(|object| ("", object)).collect(),
+            _ => vec![]
         }
     }
 
-    fn handle_sprite(new_obj: &mut Object2D, raylib: &mut Raylib, object1: &Yaml)
+    // Creates an object of the type given by its type key, or the default type when it has none,
+    // along with any objects nested under its children key. Children default to their parent's type.
+    // Returns the id of the new object.
+    fn create_object(mut raylib: Option<&mut Raylib>, object_types: &ObjectRegistry, scene: &mut Scene,
+                     default_name: &str, entry: &Yaml, default_type: &str, parent: Option<usize>) -> Option<usize>
     {
-        if let Some(file_name) = object1.as_str()
+        let unloaded = &apply_prefab(entry);
+        let type_name = unloaded["type"].as_str().unwrap_or(default_type);
+        let loader = match object_types.get(type_name)
         {
-            let asset_location: &str = &format!("assets/{}", file_name);
-            match raylib.0.load_texture(&raylib.1, asset_location)
+            Some(loader) => loader,
+            None =>
             {
-                Ok(image) => { new_obj.sprite = Some(image); }
-                Err(error) => println!("Failed to load image {} due to {}.", file_name, error)
+                log_error!("scene", "Unknown object type {:?} for {} in {}.", type_name, default_name, scene.scene_path);
+                return None;
             }
+        };
+
+        let mut new_obj = loader(raylib.as_deref_mut(), scene, unloaded);
+        // Custom loaders may not start the object where it is drawn.
+        if let Some(object2d) = new_obj.downcast_mut::<Object2D>()
+        {
+            object2d.reset_interpolation();
         }
-        else { println!("Invalid sprite file {:?}.", object1); }
+        new_obj.get_obj().parent = parent;
+        new_obj.get_obj().name = unloaded["name"].as_str().unwrap_or(default_name).to_string();
+        new_obj.get_obj().type_name = type_name.to_string();
+        new_obj.get_obj().tags = read_list(&unloaded["tags"]);
+        new_obj.get_obj().group = unloaded["group"].as_str().unwrap_or("").to_string();
+        // The entry as written, so a saved scene keeps referring to its prefabs.
+        new_obj.get_obj().loaded_properties = entry.clone();
+
+        if !unloaded["script"].is_badvalue()
+        {
+            new_obj.get_obj().scripts = read_list(&unloaded["script"]);
+            scene.script_manager.handle_script(new_obj.get_obj_ref(), &unloaded["script"]);
+        }
+
+        let id = new_obj.get_id();
+        let index = scene.objects.len();
+        scene.objects.push(new_obj);
+
+        for (name, child) in SceneManager::entries(&unloaded["children"])
+        {
+            if let Some(child_id) = SceneManager::create_object(raylib.as_deref_mut(), object_types, scene, name, child, type_name, Some(id))
+            {
+                scene.objects[index].get_obj().children.push(child_id);
+            }
+        }
+
+        Some(id)
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_support::temp_directory;
+    use std::path::PathBuf;
+
+    fn assets(name: &str, files: &[(&str, &str)]) -> PathBuf
+    {
+        let directory = temp_directory(name);
+        for (path, contents) in files
+        {
+            fs::write(directory.join(path), contents).unwrap();
+        }
+        directory
+    }
+
+    fn yaml(source: &str) -> Yaml
+    {
+        YamlLoader::load_from_str(source).unwrap().remove(0)
+    }
+
+    #[test]
+    fn prefab_properties_are_overridden()
+    {
+        let directory = assets("prefab_overrides", &[
+            ("base.yaml", "type: object2d\nname: base\nz_index: 1"),
+            ("enemy.yaml", "prefab: base.yaml\nname: enemy\ngroup: enemies"),
+        ]);
+
+        let resolved = resolve_prefab(directory.to_str().unwrap(), &yaml("prefab: enemy.yaml\nz_index: 5"), &mut vec![]);
+        assert_eq!(resolved["type"].as_str(), Some("object2d"));
+        assert_eq!(resolved["name"].as_str(), Some("enemy"));
+        assert_eq!(resolved["group"].as_str(), Some("enemies"));
+        assert_eq!(resolved["z_index"].as_i64(), Some(5));
+        assert!(resolved["prefab"].is_badvalue());
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn entries_without_a_prefab_are_unchanged()
+    {
+        let entry = yaml("name: player\nz_index: 2");
+        assert_eq!(apply_prefab(&entry), entry);
+    }
+
+    #[test]
+    fn missing_prefabs_leave_the_entry()
+    {
+        let directory = assets("prefab_missing", &[]);
+
+        let entry = yaml("prefab: missing.yaml\nname: lost");
+        assert_eq!(resolve_prefab(directory.to_str().unwrap(), &entry, &mut vec![]), entry);
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn prefab_cycles_stop()
+    {
+        let directory = assets("prefab_cycle", &[
+            ("self.yaml", "prefab: self.yaml\nname: self"),
+            ("a.yaml", "prefab: b.yaml\nname: a"),
+            ("b.yaml", "prefab: a.yaml\nz_index: 3"),
+        ]);
+        let assets = directory.to_str().unwrap();
+
+        let resolved = resolve_prefab(assets, &yaml("prefab: self.yaml"), &mut vec![]);
+        assert_eq!(resolved["name"].as_str(), Some("self"));
+
+        let resolved = resolve_prefab(assets, &yaml("prefab: a.yaml\ngroup: loop"), &mut vec![]);
+        assert_eq!(resolved["name"].as_str(), Some("a"));
+        assert_eq!(resolved["z_index"].as_i64(), Some(3));
+        assert_eq!(resolved["group"].as_str(), Some("loop"));
+        assert!(resolved["prefab"].is_badvalue());
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    fn write_scene(path: &PathBuf, contents: &str)
+    {
+        fs::write(path, contents).unwrap();
+        // Moved ahead so the change is seen even when the file system only keeps seconds.
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        fs::File::options().write(true).open(path).unwrap().set_modified(later).unwrap();
+    }
+
+    fn object_2d<'a>(scene: &'a Scene, name: &str) -> Option<&'a Object2D>
+    {
+        scene.objects.iter()
+            .find(|object| object.get_name() == name)
+            .and_then(|object| object.downcast_ref::<Object2D>())
+    }
+
+    #[test]
+    fn loads_objects_of_every_type()
+    {
+        let directory = assets("scene_load", &[]);
+        let path = directory.join("level.yaml");
+        fs::write(&path, "layers: [background, main]\nobjects:\n  - type: object2d\n    name: player\n    pos: {x: 1, y: 2}\n    layer: main\n    tags: [hero]\n    children:\n      - name: hat\n        pos: {x: 0, y: -1}\n  - type: camera\n    name: camera\n    zoom: 2\nobjects 2d:\n  wall:\n    z_index: 3").unwrap();
+
+        let mut scene_manager = SceneManager::new();
+        scene_manager.load(None, path.to_str().unwrap());
+
+        let scene = scene_manager.current_scene().unwrap();
+        assert_eq!(scene_manager.scenes.len(), 1);
+        assert_eq!(scene.render_layers, vec!["background".to_string(), "main".to_string()]);
+        assert_eq!(scene.objects.len(), 4);
+
+        let player = object_2d(scene, "player").unwrap();
+        assert_eq!(player.transform.pos, Vector2::new(1.0, 2.0));
+        assert_eq!(player.previous_pos, player.transform.pos);
+        assert_eq!(player.object.tags, vec!["hero".to_string()]);
+        assert_eq!(player.object.children.len(), 1);
+
+        let hat = object_2d(scene, "hat").unwrap();
+        assert_eq!(hat.object.parent, Some(player.get_id()));
+        assert_eq!(object_2d(scene, "wall").unwrap().z_index, 3);
+        assert!(scene.active_camera_2d().is_some());
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn push_and_overlay_stack_scenes()
+    {
+        let directory = assets("scene_stack", &[("a.yaml", "objects: []"), ("b.yaml", "objects: []")]);
+        let a = directory.join("a.yaml");
+        let b = directory.join("b.yaml");
+
+        let mut scene_manager = SceneManager::new();
+        scene_manager.load(None, a.to_str().unwrap());
+        scene_manager.overlay(None, b.to_str().unwrap());
+        assert!(!scene_manager.is_covered(0));
+        scene_manager.push(None, b.to_str().unwrap());
+        assert!(scene_manager.is_covered(0));
+        assert_eq!(scene_manager.scenes.len(), 3);
+
+        scene_manager.load(None, a.to_str().unwrap());
+        assert_eq!(scene_manager.scenes.len(), 1);
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn hot_reload_applies_scene_changes()
+    {
+        let directory = assets("scene_reload", &[]);
+        let path = directory.join("level.yaml");
+        fs::write(&path, "objects:\n  - type: object2d\n    name: player\n    pos: {x: 1, y: 2}\n    z_index: 1\n  - type: object2d\n    name: rock\n  - type: object2d\n    name: tree\n    pos: {x: 5, y: 5}").unwrap();
+
+        let mut scene_manager = SceneManager::new();
+        scene_manager.load(None, path.to_str().unwrap());
+        let player_id = object_2d(scene_manager.current_scene().unwrap(), "player").unwrap().get_id();
+        let tree_id = object_2d(scene_manager.current_scene().unwrap(), "tree").unwrap().get_id();
+
+        // Moved at runtime, which a change to another of its keys should keep.
+        scene_manager.scenes[0].objects.iter_mut()
+            .find_map(|object| object.downcast_mut::<Object2D>().filter(|object| object.object.name == "player"))
+            .unwrap().transform.pos = Vector2::new(10.0, 20.0);
+
+        // Nothing changed yet.
+        scene_manager.reload_changed_assets(None);
+        assert_eq!(scene_manager.scenes[0].objects.len(), 3);
+
+        write_scene(&path, "objects:\n  - type: object2d\n    name: player\n    pos: {x: 1, y: 2}\n    z_index: 4\n  - type: object2d\n    name: tree\n    pos: {x: 5, y: 5}\n  - type: object2d\n    name: bird\n    pos: {x: 7, y: 8}");
+        scene_manager.reload_changed_assets(None);
+
+        let scene = scene_manager.current_scene().unwrap();
+        assert_eq!(scene.objects.len(), 3);
+        assert!(object_2d(scene, "rock").is_none());
+
+        let player = object_2d(scene, "player").unwrap();
+        assert_eq!(player.get_id(), player_id);
+        assert_eq!(player.z_index, 4);
+        assert_eq!(player.transform.pos, Vector2::new(10.0, 20.0));
+
+        assert_eq!(object_2d(scene, "tree").unwrap().get_id(), tree_id);
+        assert_eq!(object_2d(scene, "bird").unwrap().transform.pos, Vector2::new(7.0, 8.0));
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn hot_reload_adds_and_removes_objects_in_one_pass()
+    {
+        let directory = assets("scene_reload_add_remove", &[]);
+        let path = directory.join("level.yaml");
+        fs::write(&path, "objects:\n  - type: object2d\n    name: rock\n    children:\n      - name: moss\n  - type: object2d\n    name: tree\n  - type: object2d\n    name: player").unwrap();
+
+        let mut scene_manager = SceneManager::new();
+        scene_manager.load(None, path.to_str().unwrap());
+        let player_id = object_2d(scene_manager.current_scene().unwrap(), "player").unwrap().get_id();
+
+        // The rock and its child go, the tree becomes a camera, which destroys it after the
+        // bird and its children were added, and the player is changed in place.
+        write_scene(&path, "objects:\n  - type: object2d\n    name: bird\n    children:\n      - name: wing\n      - name: beak\n  - type: camera\n    name: tree\n  - type: object2d\n    name: player\n    z_index: 2");
+        scene_manager.reload_changed_assets(None);
+
+        let scene = scene_manager.current_scene().unwrap();
+        assert_eq!(scene.objects.len(), 5);
+        assert!(object_2d(scene, "rock").is_none());
+        assert!(object_2d(scene, "moss").is_none());
+        assert!(object_2d(scene, "tree").is_none());
+        assert!(scene.active_camera_2d().is_some());
+
+        let bird = object_2d(scene, "bird").unwrap();
+        assert_eq!(bird.object.children.len(), 2);
+        assert_eq!(object_2d(scene, "wing").unwrap().object.parent, Some(bird.get_id()));
+        assert_eq!(object_2d(scene, "beak").unwrap().object.parent, Some(bird.get_id()));
+
+        let player = object_2d(scene, "player").unwrap();
+        assert_eq!(player.get_id(), player_id);
+        assert_eq!(player.z_index, 2);
+
+        let _ = fs::remove_dir_all(&directory);
     }
 
+    #[test]
+    fn saved_scenes_load_back_the_same()
+    {
+        let directory = assets("scene_save", &[]);
+        let path = directory.join("level.yaml");
+        let saved = directory.join("saved.yaml");
+        fs::write(&path, "layers: [main]\nmusic: theme.ogg\nobjects:\n  - type: object2d\n    name: player\n    pos: {x: 1.5, y: 2}\n    script: [move.dry, shoot.dry]\n    children:\n      - name: hat\n        pos: {x: 0, y: -1}\nobjects 2d:\n  wall:\n    z_index: 3\n    note: keep me\ncameras 2d:\n  view:\n    zoom: 2").unwrap();
+
+        let mut scene_manager = SceneManager::new();
+        scene_manager.load(None, path.to_str().unwrap());
+        scene_manager.current_scene().unwrap().save(saved.to_str().unwrap()).unwrap();
+
+        let saved_yaml = yaml(&fs::read_to_string(&saved).unwrap());
+        assert!(saved_yaml["objects 2d"].is_badvalue());
+        assert!(saved_yaml["cameras 2d"].is_badvalue());
+        assert_eq!(saved_yaml["music"].as_str(), Some("theme.ogg"));
+
+        let mut reloaded = SceneManager::new();
+        reloaded.load(None, saved.to_str().unwrap());
+
+        let before = scene_manager.current_scene().unwrap();
+        let after = reloaded.current_scene().unwrap();
+        assert_eq!(after.render_layers, before.render_layers);
+        assert_eq!(after.objects.len(), before.objects.len());
+        for object in &before.objects
+        {
+            let obj = object.get_obj_ref();
+            let copy = after.objects.iter().find(|copy| copy.get_name() == obj.name).unwrap().get_obj_ref();
+            assert_eq!(copy.type_name, obj.type_name);
+            assert_eq!(copy.scripts, obj.scripts);
+            assert_eq!(copy.children.len(), obj.children.len());
+            assert_eq!(copy.parent.is_some(), obj.parent.is_some());
+        }
+
+        let player = object_2d(after, "player").unwrap();
+        assert_eq!(player.transform.pos, Vector2::new(1.5, 2.0));
+        assert_eq!(player.object.scripts, vec!["move.dry".to_string(), "shoot.dry".to_string()]);
+
+        let hat = object_2d(after, "hat").unwrap();
+        assert_eq!(hat.object.parent, Some(player.get_id()));
+        assert_eq!(hat.transform.pos, Vector2::new(0.0, -1.0));
+
+        let wall = object_2d(after, "wall").unwrap();
+        assert_eq!(wall.z_index, 3);
+        assert_eq!(wall.object.loaded_properties["note"].as_str(), Some("keep me"));
+        assert_eq!(after.active_camera_2d().unwrap().zoom(), 2.0);
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn sounds_are_kept_when_saved()
+    {
+        let directory = assets("scene_sound", &[]);
+        let path = directory.join("level.yaml");
+        let saved = directory.join("saved.yaml");
+        fs::write(&path, "objects:\n  - type: sound\n    name: theme\n    file: theme.ogg\n    volume: 0.5").unwrap();
+
+        let mut scene_manager = SceneManager::new();
+        scene_manager.load(None, path.to_str().unwrap());
+        let scene = scene_manager.current_scene().unwrap();
+        assert_eq!(scene.objects.len(), 1);
+        assert_eq!(scene.objects[0].get_name(), "theme");
+
+        scene.save(saved.to_str().unwrap()).unwrap();
+        let sound = &yaml(&fs::read_to_string(&saved).unwrap())["objects"][0];
+        assert_eq!(sound["type"].as_str(), Some("sound"));
+        assert_eq!(sound["file"].as_str(), Some("theme.ogg"));
+        assert_eq!(sound["volume"].as_f64(), Some(0.5));
+
+        let _ = fs::remove_dir_all(&directory);
+    }
 }
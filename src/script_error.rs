@@ -0,0 +1,177 @@
+use crate::log_error;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+// An error raised by a script, along with where it happened.
+#[derive(Clone, Debug)]
+pub struct ScriptError
+{
+    pub script: String,
+    // Empty when the script failed to parse.
+    pub function: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub object_name: String,
+    pub object_id: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}", self.script)?;
+        if let Some(line) = self.line
+        {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column
+            {
+                write!(f, ":{}", column)?;
+            }
+        }
+        if !self.function.is_empty()
+        {
+            write!(f, " in {}", self.function)?;
+        }
+
+        write!(f, " ({} #{}): {}", self.object_name, self.object_id, self.message)
+    }
+}
+
+// What happens to a script once it fails.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorPolicy
+{
+    // The script is removed and its object carries on without it.
+    Disable,
+    // The error is reported and the script is called again next time.
+    KeepRunning,
+    // The game stops after the current frame.
+    Halt,
+}
+
+impl ErrorPolicy
+{
+    pub fn from_name(name: &str) -> Option<Self>
+    {
+        match name
+        {
+            "disable" => Some(ErrorPolicy::Disable),
+            "keep_running" => Some(ErrorPolicy::KeepRunning),
+            "halt" => Some(ErrorPolicy::Halt),
+            _ => None,
+        }
+    }
+}
+
+// Receives every script error, such as to show them in game. They are logged by default.
+pub type ErrorSink = Box<dyn FnMut(&ScriptError)>;
+
+thread_local!
+{
+    static ERROR_SINK: RefCell<ErrorSink> = RefCell::new(Box::new(|error| log_error!("script", "{}", error)));
+    static ERROR_POLICY: Cell<ErrorPolicy> = Cell::new(ErrorPolicy::Disable);
+    static HALT_REQUESTED: Cell<bool> = Cell::new(false);
+}
+
+pub fn set_error_sink(sink: ErrorSink)
+{
+    ERROR_SINK.with(|current| *current.borrow_mut() = sink);
+}
+
+pub fn set_error_policy(policy: ErrorPolicy)
+{
+    ERROR_POLICY.with(|current| current.set(policy));
+}
+
+pub fn error_policy() -> ErrorPolicy
+{
+    ERROR_POLICY.with(|current| current.get())
+}
+
+// Sends the error to the sink, and asks the game to stop when the policy is to halt.
+pub fn report(error: &ScriptError)
+{
+    ERROR_SINK.with(|sink| (sink.borrow_mut())(error));
+
+    if error_policy() == ErrorPolicy::Halt
+    {
+        HALT_REQUESTED.with(|halt| halt.set(true));
+    }
+}
+
+pub fn halt_requested() -> bool
+{
+    HALT_REQUESTED.with(|halt| halt.get())
+}
+
+pub fn clear_halt()
+{
+    HALT_REQUESTED.with(|halt| halt.set(false));
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use std::rc::Rc;
+
+    fn error() -> ScriptError
+    {
+        ScriptError
+        {
+            script: "player.dry".to_string(),
+            function: "update".to_string(),
+            line: Some(12),
+            column: Some(4),
+            object_name: "player".to_string(),
+            object_id: 3,
+            message: "Unknown variable speed".to_string(),
+        }
+    }
+
+    #[test]
+    fn policies_are_read_by_name()
+    {
+        assert_eq!(ErrorPolicy::from_name("disable"), Some(ErrorPolicy::Disable));
+        assert_eq!(ErrorPolicy::from_name("keep_running"), Some(ErrorPolicy::KeepRunning));
+        assert_eq!(ErrorPolicy::from_name("halt"), Some(ErrorPolicy::Halt));
+        assert_eq!(ErrorPolicy::from_name("Halt"), None);
+        assert_eq!(ErrorPolicy::from_name(""), None);
+    }
+
+    #[test]
+    fn display_includes_the_location()
+    {
+        assert_eq!(error().to_string(), "player.dry:12:4 in update (player #3): Unknown variable speed");
+
+        let mut parse_error = error();
+        parse_error.function = String::new();
+        parse_error.column = None;
+        assert_eq!(parse_error.to_string(), "player.dry:12 (player #3): Unknown variable speed");
+
+        parse_error.line = None;
+        parse_error.column = Some(4);
+        assert_eq!(parse_error.to_string(), "player.dry (player #3): Unknown variable speed");
+    }
+
+    #[test]
+    fn report_sends_to_the_sink_and_halts_by_policy()
+    {
+        let received = Rc::new(RefCell::new(vec![]));
+        let sink_received = received.clone();
+        set_error_sink(Box::new(move |error| sink_received.borrow_mut().push(error.to_string())));
+
+        set_error_policy(ErrorPolicy::KeepRunning);
+        report(&error());
+        assert!(!halt_requested());
+
+        set_error_policy(ErrorPolicy::Halt);
+        report(&error());
+        assert!(halt_requested());
+        clear_halt();
+        assert!(!halt_requested());
+
+        assert_eq!(received.borrow().len(), 2);
+    }
+}
@@ -1,6 +1,11 @@
-use crate::object::{Object, TObject};
+use crate::{log_error, log_info, log_warn};
+use crate::object::{Object, TObject, with_object_info};
+use crate::script_error::{self, ScriptError, ErrorPolicy};
 use std::collections::HashMap;
+use std::time::SystemTime;
 use drython::types::Parser;
+use crate::drython_extensions::{register_global_functions, get_parent, get_children, find_child, destroy, instantiate, spawn, script_logger};
+use crate::logging::Level;
 use yaml_rust::Yaml;
 
 use drython::types::error::ErrorManager;
@@ -10,7 +15,11 @@ pub type Script = (String, Runner, ErrorManager);
 
 pub struct ScriptManager
 {
-    pub scripts: HashMap<usize, Script>,
+    // Every script of an object, in the order they are listed and run in.
+    pub scripts: HashMap<usize, Vec<Script>>,
+
+    // When each script file was last changed, to know when to reload it.
+    modified: HashMap<String, SystemTime>,
 }
 
 impl ScriptManager
@@ -20,21 +29,116 @@ impl ScriptManager
         ScriptManager
         {
             scripts: HashMap::new(),
+            modified: HashMap::new(),
+        }
+    }
+
+    // Sets up the scripts object by object in the order they were added, like run_function_all,
+    // so setups that draw random numbers get the same ones when a session is replayed.
+    pub fn run_setup(&mut self, objects: &mut Vec<Box<dyn TObject>>)
+    {
+        let ids: Vec<usize> = objects.iter().map(|object| object.get_id()).collect();
+        for id in ids
+        {
+            self.setup_script(objects, id);
+        }
+    }
+
+    // Sets up every script of the object. Like any other call, each runner is given the
+    // object's variables first when it shares them, and they are read back after.
+    pub fn setup_script(&mut self, objects: &mut Vec<Box<dyn TObject>>, id: usize)
+    {
+        let object = match objects.iter_mut().find(|object| object.get_id() == id)
+        {
+            Some(object) => object,
+            None => return,
+        };
+
+        let mut failed = vec![];
+        if let Some(scripts) = self.scripts.get_mut(&id)
+        {
+            let shared = scripts.len() > 1;
+            for (index, script) in scripts.iter_mut().enumerate()
+            {
+                if ScriptManager::setup(script, object, shared, id)
+                {
+                    failed.push((id, index));
+                }
+            }
+        }
+
+        self.disable(failed);
+    }
+
+    // Runs one script's setup, true when the script should be disabled.
+    fn setup(script: &mut Script, object: &mut Box<dyn TObject>, shared: bool, id: usize) -> bool
+    {
+        if shared
+        {
+            script.1.register_variables(object.get_drython_vars());
         }
+
+        script.1.run_setup(&mut script.2);
+        object.set_my_vars(&mut script.1, "");
+
+        ScriptManager::report_script_errors(script, id, "setup")
+    }
+
+    // Reports each of the errors left in the error manager to the error sink, then clears them.
+    fn report_errors(script_path: &str, function: &str, id: usize, object_name: &str, error_manager: &mut ErrorManager)
+    {
+        for error in &error_manager.errors
+        {
+            script_error::report(&ScriptError
+            {
+                script: script_path.to_string(),
+                function: function.to_string(),
+                line: Some(error.line),
+                column: Some(error.column),
+                object_name: object_name.to_string(),
+                object_id: id,
+                message: error.message.clone(),
+            });
+        }
+
+        error_manager.errors.clear();
+    }
+
+    // Reports the errors the script's last call left, true when the policy is to disable it.
+    fn report_script_errors(script: &mut Script, id: usize, function: &str) -> bool
+    {
+        if script.2.errors.is_empty()
+        {
+            return false;
+        }
+
+        let object_name = with_object_info(id, |info| info.name.clone()).unwrap_or_default();
+        ScriptManager::report_errors(&script.0, function, id, &object_name, &mut script.2);
+
+        script_error::error_policy() == ErrorPolicy::Disable
     }
 
-    pub fn run_setup(&mut self)
+    // Removes scripts by object id and their index in the object's list.
+    fn disable(&mut self, failed: Vec<(usize, usize)>)
     {
-        for script in &mut self.scripts
+        for script in remove_scripts(&mut self.scripts, failed)
         {
-            script.1.1.run_setup(&mut script.1.2);
-            println!("{:#?}", script.1.1.parser.global_expressions);
+            log_warn!("script", "{} has been disabled.", script.0);
         }
     }
 
-    // Adds a new script to the list from a .dry file.
+    // Adds the object's scripts from a .dry file, or a list of them.
     pub fn handle_script(& mut self, new_obj: &Object, script_path: &Yaml)
     {
+        if let Yaml::Array(script_paths) = script_path
+        {
+            for script_path in script_paths
+            {
+                self.handle_script(new_obj, script_path);
+            }
+            return;
+        }
+
         if let Some(file_name) = script_path.as_str()
         {
             if let Ok(canon) = std::fs::canonicalize(format!("assets/{}", file_name))
@@ -49,53 +153,191 @@ impl ScriptManager
                         {
                             if error_manager.errors.len() == 0
                             {
-                                self.scripts.insert(new_obj.get_id(), (full_path.to_string(), Runner::new(parser), error_manager));
+                                if let Some(modified) = ScriptManager::modified_time(full_path)
+                                {
+                                    self.modified.insert(full_path.to_string(), modified);
+                                }
+                                self.scripts.entry(new_obj.get_id()).or_default()
+                                    .push((full_path.to_string(), Runner::new(parser), error_manager));
                             }
                             else
                             {
-                                println!("Script parsed with errors. Please fix them before your script becomes active:\n{:#?}",
-                                         error_manager.errors);
+                                // A script that does not parse can not run, whatever the policy.
+                                ScriptManager::report_errors(full_path, "", new_obj.get_id(), &new_obj.name, &mut error_manager);
                             }
                         }
-                        Err(error) => println!("Failed to load script {} due to {}.", file_name, error)
+                        Err(error) => script_error::report(&ScriptError
+                        {
+                            script: full_path.to_string(),
+                            function: String::new(),
+                            line: None,
+                            column: None,
+                            object_name: new_obj.name.clone(),
+                            object_id: new_obj.get_id(),
+                            message: error.to_string(),
+                        }),
                     }
                 }
                 else
                 {
-                    println!("Failed to load script from {:?}. Path contains non-unicode characters.", canon);
+                    log_error!("script", "Failed to load script from {:?}. Path contains non-unicode characters.", canon);
                 }
             }
             else
             {
-                println!("Failed to load script from {:?}. Path is not local to asset folder.", file_name);
+                log_error!("script", "Failed to load script from {:?}. Path is not local to asset folder.", file_name);
             }
         }
-        else { println!("Invalid sprite file {:?}.", script_path); }
+        else { log_warn!("script", "Invalid script file {:?}.", script_path); }
     }
 
     pub fn register_externals(&mut self, objects: &mut Vec<Box<dyn TObject>>)
     {
         for object in objects.iter_mut()
         {
-            if self.scripts.contains_key(&object.get_id())
+            if let Some(scripts) = self.scripts.get_mut(&object.get_id())
+            {
+                for script in scripts
+                {
+                    ScriptManager::register_object_externals(&mut script.1, &script.0, object);
+                }
+            }
+        }
+    }
+
+    // Registers the object's variables and the functions one of its scripts can call.
+    fn register_object_externals(runner: &mut Runner, script_path: &str, object: &mut Box<dyn TObject>)
+    {
+        // Variables
+        runner.register_variables(object.get_drython_vars());
+
+        // Functions.
+        runner.register_external_function("register_input", Some(&mut *object), Box::new(Object::register_input));
+        runner.register_external_function("get_parent", Some(&mut *object), Box::new(get_parent));
+        runner.register_external_function("get_children", Some(&mut *object), Box::new(get_children));
+        runner.register_external_function("find_child", Some(&mut *object), Box::new(find_child));
+        runner.register_external_function("destroy", Some(&mut *object), Box::new(destroy));
+        runner.register_external_function("instantiate", Some(&mut *object), Box::new(instantiate));
+        runner.register_external_function("spawn", Some(&mut *object), Box::new(spawn));
+        runner.register_external_function("log_info", Some(&mut *object), Box::new(script_logger(Level::Info, script_path.to_string())));
+        runner.register_external_function("log_warn", Some(&mut *object), Box::new(script_logger(Level::Warn, script_path.to_string())));
+        runner.register_external_function("log_error", Some(&mut *object), Box::new(script_logger(Level::Error, script_path.to_string())));
+        register_global_functions(runner);
+    }
+
+    // Gives every runner of the object its current variables, after the engine or
+    // another script changed them.
+    pub fn share_variables(&mut self, object: &mut Box<dyn TObject>)
+    {
+        if let Some(scripts) = self.scripts.get_mut(&object.get_id())
+        {
+            for script in scripts
             {
-                // Variables
-                let obj_map = object.get_drython_vars();
+                script.1.register_variables(object.get_drython_vars());
+            }
+        }
+    }
+
+    fn modified_time(path: &str) -> Option<SystemTime>
+    {
+        std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    // Parses every script file that changed since it was loaded and swaps in the new version
+    // for each object using it, then calls on_reload. A version with errors is reported and
+    // the old one keeps running.
+    pub fn reload_changed_scripts(&mut self, objects: &mut Vec<Box<dyn TObject>>)
+    {
+        let mut changed: Vec<String> = self.scripts.values().flatten().map(|script| script.0.clone()).collect();
+        changed.sort();
+        changed.dedup();
+        changed.retain(|path|
+        {
+            let modified = ScriptManager::modified_time(path);
+            modified.is_some() && modified != self.modified.get(path).copied()
+        });
 
-                // Functions.
-                
-                self.scripts.entry(object.get_id())
-                    .and_modify(|x|
+        for path in changed
+        {
+            if let Some(modified) = ScriptManager::modified_time(&path)
+            {
+                // Remembered even when the new version fails, so it is only reported once.
+                self.modified.insert(path.clone(), modified);
+            }
+
+            // Each use is the object and which of its copies of the script it is. Indexes
+            // are looked up when the copy is swapped, as on_reload can disable scripts.
+            let uses: Vec<(usize, usize)> = self.scripts.iter()
+                .flat_map(|(id, scripts)| (0..scripts.iter().filter(|script| script.0 == path).count())
+                    .map(move |copy| (*id, copy)))
+                .collect();
+            for (id, copy) in uses
+            {
+                let mut error_manager = ErrorManager::new();
+                let parser = match Parser::parse_file(&path, &mut error_manager)
+                {
+                    Ok(parser) if error_manager.errors.len() == 0 => parser,
+                    Ok(_) =>
                     {
-                        x.1.register_variables(obj_map);
-                        x.1.register_external_function("register_input", Some(object), Box::new(Object::register_input));
+                        log_warn!("script", "Script {} changed but has errors, the previous version keeps running until they are fixed.", path);
+                        let object_name = with_object_info(id, |info| info.name.clone()).unwrap_or_default();
+                        ScriptManager::report_errors(&path, "", id, &object_name, &mut error_manager);
+                        break;
                     }
-                );
-            }
+                    Err(error) =>
+                    {
+                        log_error!("script", "Failed to reload script {} due to {}, the previous version keeps running.", path, error);
+                        break;
+                    }
+                };
 
+                let object = match objects.iter_mut().find(|object| object.get_id() == id)
+                {
+                    Some(object) => object,
+                    None => continue,
+                };
+
+                let index = match self.scripts.get(&id).and_then(|scripts| scripts.iter()
+                    .enumerate()
+                    .filter(|(_, script)| script.0 == path)
+                    .nth(copy))
+                {
+                    Some((index, _)) => index,
+                    None => continue,
+                };
+                let shared = self.scripts[&id].len() > 1;
+
+                let mut runner = Runner::new(parser);
+                ScriptManager::register_object_externals(&mut runner, &path, object);
+                runner.run_setup(&mut error_manager);
+                let mut script = (path.clone(), runner, error_manager);
+                // The old version is kept whatever the error policy, which only decides
+                // what happens to scripts that fail while running.
+                if !script.2.errors.is_empty()
+                {
+                    ScriptManager::report_script_errors(&mut script, id, "setup");
+                    log_warn!("script", "Reloaded script {} failed to set up, the previous version keeps running.", path);
+                    continue;
+                }
+
+                log_info!("script", "Reloaded script {} for {}.", path, object.get_name());
+                object.set_my_vars(&mut script.1, "");
+                let slot = &mut self.scripts.get_mut(&id).unwrap()[index];
+                *slot = script;
+
+                if ScriptManager::has_function(&slot.1, "on_reload")
+                    && ScriptManager::call(slot, object, shared, id, "on_reload", vec![])
+                {
+                    self.disable(vec![(id, index)]);
+                }
+            }
         }
+
+        self.update_variables(objects);
     }
 
+    // Reads back the variables of objects with a single script. Objects with several
+    // scripts read back after each call instead, as their runners take turns.
     pub fn update_variables<T>(&mut self, objects: &mut Vec<Box<T>>)
         where
             T: TObject,
@@ -103,32 +345,169 @@ impl ScriptManager
     {
         for object in objects
         {
-            if self.scripts.contains_key(&object.get_id())
+            if let Some([script]) = self.scripts.get_mut(&object.get_id()).map(|scripts| scripts.as_mut_slice())
             {
-                self.scripts.entry(object.get_id()).and_modify(|x| object.set_my_vars(&mut x.1, ""));
+                object.set_my_vars(&mut script.1, "");
             }
         }
     }
 
-    pub fn run_function_all(&mut self, name: &str, args: Option<Vec<Token>>)
+    // Calls the function in every script that has it, object by object in the order they
+    // were added, and each object's scripts in the order they are listed.
+    pub fn run_function_all(&mut self, objects: &mut Vec<Box<dyn TObject>>, name: &str, args: Option<Vec<Token>>)
     {
-        let mut delete_me = None;
-        for script in &mut self.scripts
+        let mut failed = vec![];
+        for object in objects.iter_mut()
         {
-            script.1.1.call_function(name, args.clone().unwrap_or(vec![]), &mut script.1.2);
+            let id = object.get_id();
+            let scripts = match self.scripts.get_mut(&id)
+            {
+                Some(scripts) => scripts,
+                None => continue,
+            };
 
-            if script.1.2.errors.len() > 0
+            let shared = scripts.len() > 1;
+            for (index, script) in scripts.iter_mut().enumerate()
             {
-                println!("{} failed to call {}, due to the following errors:\n{:#?}\nWarning: Script has been disabled.",
-                         script.1.0, name, script.1.2.errors);
+                // Callbacks are optional, scripts only implement the ones they need.
+                if !ScriptManager::has_function(&script.1, name)
+                {
+                    continue;
+                }
+
+                if ScriptManager::call(script, object, shared, id, name, args.clone().unwrap_or(vec![]))
+                {
+                    failed.push((id, index));
+                }
+            }
+        }
+
+        self.disable(failed);
+    }
+
+    // Calls a function on each of a single object's scripts that has it, in the order they
+    // are listed. Failed scripts are disabled once all of them ran, like in run_function_all.
+    pub fn run_function(&mut self, objects: &mut Vec<Box<dyn TObject>>, id: usize, name: &str, args: Vec<Token>)
+    {
+        let object = match objects.iter_mut().find(|object| object.get_id() == id)
+        {
+            Some(object) => object,
+            None => return,
+        };
 
-                delete_me = Some(*script.0);
+        let mut failed = vec![];
+        if let Some(scripts) = self.scripts.get_mut(&id)
+        {
+            let shared = scripts.len() > 1;
+            for (index, script) in scripts.iter_mut().enumerate()
+            {
+                if ScriptManager::has_function(&script.1, name)
+                    && ScriptManager::call(script, object, shared, id, name, args.clone())
+                {
+                    failed.push((id, index));
+                }
             }
         }
 
-        if let Some(delete) = delete_me
+        self.disable(failed);
+    }
+
+    // Calls one script's function, true when the script should be disabled. When the object
+    // has several scripts the runner is given the object's variables first and they are read
+    // back after, so each script sees the changes the others made.
+    fn call(script: &mut Script, object: &mut Box<dyn TObject>, shared: bool, id: usize, name: &str, args: Vec<Token>) -> bool
+    {
+        if shared
+        {
+            script.1.register_variables(object.get_drython_vars());
+        }
+
+        script.1.call_function(name, args, &mut script.2);
+
+        if shared
+        {
+            object.set_my_vars(&mut script.1, "");
+        }
+
+        ScriptManager::report_script_errors(script, id, name)
+    }
+
+    pub fn has_function(runner: &Runner, name: &str) -> bool
+    {
+        runner.parser.functions.contains_key(name)
+    }
+}
+
+// Removes scripts by object id and their index in the object's list, and objects left
+// without any. The indexes are the ones from before any was removed.
+fn remove_scripts<T>(scripts: &mut HashMap<usize, Vec<T>>, mut failed: Vec<(usize, usize)>) -> Vec<T>
+{
+    let mut removed = vec![];
+
+    // Later scripts first, so the indexes of the earlier ones stay the same.
+    failed.sort();
+    failed.dedup();
+    for (id, index) in failed.into_iter().rev()
+    {
+        if let Some(object_scripts) = scripts.get_mut(&id)
         {
-            self.scripts.remove(&delete);
+            if index < object_scripts.len()
+            {
+                removed.push(object_scripts.remove(index));
+            }
+
+            if object_scripts.is_empty()
+            {
+                scripts.remove(&id);
+            }
         }
     }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn scripts(lists: &[(usize, &[&'static str])]) -> HashMap<usize, Vec<&'static str>>
+    {
+        lists.iter().map(|(id, list)| (*id, list.to_vec())).collect()
+    }
+
+    #[test]
+    fn removing_a_middle_script_keeps_the_others_in_order()
+    {
+        let mut all = scripts(&[(1, &["a.dry", "b.dry", "c.dry"])]);
+
+        let removed = remove_scripts(&mut all, vec![(1, 1)]);
+
+        assert_eq!(removed, vec!["b.dry"]);
+        assert_eq!(all[&1], vec!["a.dry", "c.dry"]);
+    }
+
+    #[test]
+    fn indexes_are_from_before_any_removal()
+    {
+        let mut all = scripts(&[(1, &["a.dry", "b.dry", "c.dry", "d.dry"]), (2, &["e.dry", "f.dry"])]);
+
+        // Unsorted, as failures are collected object by object.
+        let removed = remove_scripts(&mut all, vec![(1, 1), (2, 0), (1, 3)]);
+
+        assert_eq!(removed, vec!["e.dry", "d.dry", "b.dry"]);
+        assert_eq!(all[&1], vec!["a.dry", "c.dry"]);
+        assert_eq!(all[&2], vec!["f.dry"]);
+    }
+
+    #[test]
+    fn objects_without_scripts_left_are_removed()
+    {
+        let mut all = scripts(&[(1, &["a.dry", "b.dry"]), (2, &["c.dry"])]);
+
+        remove_scripts(&mut all, vec![(1, 0), (1, 1), (1, 1)]);
+
+        assert!(!all.contains_key(&1));
+        assert_eq!(all[&2], vec!["c.dry"]);
+    }
 }
@@ -0,0 +1,13 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// An empty directory for one test, named after it so tests running at the same time
+// do not share files. Tests remove it once they are done.
+pub fn temp_directory(name: &str) -> PathBuf
+{
+    let directory = env::temp_dir().join(format!("drygon_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
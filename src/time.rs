@@ -0,0 +1,54 @@
+use std::cell::Cell;
+
+// Largest time scale accepted, so a typo in a script can not make a frame run for hours.
+pub const MAX_TIME_SCALE: f32 = 100.0;
+
+// The time scale is shared with scripts through external functions, so it lives
+// outside of the Game.
+thread_local!
+{
+    static TIME_SCALE: Cell<f32> = Cell::new(1.0);
+}
+
+pub fn time_scale() -> f32
+{
+    TIME_SCALE.with(|scale| scale.get())
+}
+
+// A scale of 0 pauses updates. Negative and non finite scales are rejected and
+// the current one is kept, scales above MAX_TIME_SCALE are clamped to it.
+pub fn set_time_scale(scale: f32) -> Result<(), String>
+{
+    if !scale.is_finite() || scale < 0.0
+    {
+        return Err(format!("Invalid time scale {}, it must be a number of 0 or more.", scale));
+    }
+
+    TIME_SCALE.with(|current| current.set(scale.min(MAX_TIME_SCALE)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_scales()
+    {
+        set_time_scale(2.0).unwrap();
+        assert!(set_time_scale(-1.0).is_err());
+        assert!(set_time_scale(f32::NAN).is_err());
+        assert!(set_time_scale(f32::INFINITY).is_err());
+        assert_eq!(time_scale(), 2.0);
+    }
+
+    #[test]
+    fn clamps_large_scales()
+    {
+        set_time_scale(1.0e9).unwrap();
+        assert_eq!(time_scale(), MAX_TIME_SCALE);
+        set_time_scale(0.0).unwrap();
+        assert_eq!(time_scale(), 0.0);
+    }
+}
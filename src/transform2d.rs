@@ -1,6 +1,7 @@
 use crate::transform::Transform;
 use raylib::prelude::Vector2;
 
+#[derive(Clone, Copy, Debug)]
 pub struct Transform2D
 {
     pub pos: Vector2,
@@ -8,7 +9,83 @@ pub struct Transform2D
     pub scale: Vector2
 }
 
+impl Transform2D
+{
+    // Places a child transform, given relative to this one, into this transform's space.
+    // Rotation is in degrees.
+    pub fn combine(&self, child: &Transform2D) -> Transform2D
+    {
+        let scaled = Vector2::new(child.pos.x * self.scale.x, child.pos.y * self.scale.y);
+        let (sin, cos) = self.rot.to_radians().sin_cos();
+
+        Transform2D
+        {
+            pos: Vector2::new(
+                self.pos.x + scaled.x * cos - scaled.y * sin,
+                self.pos.y + scaled.x * sin + scaled.y * cos),
+            rot: self.rot + child.rot,
+            scale: Vector2::new(self.scale.x * child.scale.x, self.scale.y * child.scale.y),
+        }
+    }
+}
+
 impl Transform for Transform2D
 {
 
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn transform(x: f32, y: f32, rot: f32, scale_x: f32, scale_y: f32) -> Transform2D
+    {
+        Transform2D { pos: Vector2::new(x, y), rot, scale: Vector2::new(scale_x, scale_y) }
+    }
+
+    fn assert_close(a: f32, b: f32)
+    {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn child_position_is_scaled_and_rotated_by_the_parent()
+    {
+        let parent = transform(10.0, 5.0, 90.0, 2.0, 3.0);
+        let child = transform(1.0, 1.0, 0.0, 1.0, 1.0);
+
+        let combined = parent.combine(&child);
+
+        // (1, 1) scaled to (2, 3), then rotated a quarter turn to (-3, 2).
+        assert_close(combined.pos.x, 7.0);
+        assert_close(combined.pos.y, 7.0);
+    }
+
+    #[test]
+    fn rotation_adds_and_scale_multiplies()
+    {
+        let parent = transform(0.0, 0.0, 30.0, 2.0, 0.5);
+        let child = transform(0.0, 0.0, 15.0, 3.0, 4.0);
+
+        let combined = parent.combine(&child);
+
+        assert_close(combined.rot, 45.0);
+        assert_close(combined.scale.x, 6.0);
+        assert_close(combined.scale.y, 2.0);
+    }
+
+    #[test]
+    fn combining_with_the_identity_keeps_the_child()
+    {
+        let child = transform(4.0, -2.0, 12.0, 1.5, 2.5);
+
+        let combined = transform(0.0, 0.0, 0.0, 1.0, 1.0).combine(&child);
+
+        assert_close(combined.pos.x, 4.0);
+        assert_close(combined.pos.y, -2.0);
+        assert_close(combined.rot, 12.0);
+        assert_close(combined.scale.x, 1.5);
+        assert_close(combined.scale.y, 2.5);
+    }
+}
@@ -2,6 +2,7 @@ use crate::transform::Transform;
 use raylib::math::Quaternion;
 use raylib::math::Vector3;
 
+#[derive(Clone, Copy, Debug)]
 pub struct Transform3D
 {
     pub pos: Vector3,
@@ -9,7 +10,90 @@ pub struct Transform3D
     pub scale: Vector3
 }
 
+impl Transform3D
+{
+    // Places a child transform, given relative to this one, into this transform's space.
+    pub fn combine(&self, child: &Transform3D) -> Transform3D
+    {
+        let scaled = Vector3::new(child.pos.x * self.scale.x, child.pos.y * self.scale.y, child.pos.z * self.scale.z);
+
+        Transform3D
+        {
+            pos: self.pos + scaled.rotate_by(self.rot),
+            rot: multiply(self.rot, child.rot),
+            scale: Vector3::new(self.scale.x * child.scale.x, self.scale.y * child.scale.y, self.scale.z * child.scale.z),
+        }
+    }
+}
+
+// Hamilton product, applies b then a.
+fn multiply(a: Quaternion, b: Quaternion) -> Quaternion
+{
+    Quaternion::new(
+        a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+        a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+        a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z)
+}
+
 impl Transform for Transform3D
 {
 
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn transform(pos: Vector3, rot: Quaternion, scale: Vector3) -> Transform3D
+    {
+        Transform3D { pos, rot, scale }
+    }
+
+    fn quarter_turn_y() -> Quaternion
+    {
+        Quaternion::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), std::f32::consts::FRAC_PI_2)
+    }
+
+    fn assert_close(a: Vector3, b: Vector3)
+    {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn child_position_is_scaled_and_rotated_by_the_parent()
+    {
+        let parent = transform(Vector3::new(1.0, 2.0, 3.0), quarter_turn_y(), Vector3::new(2.0, 1.0, 1.0));
+        let child = transform(Vector3::new(1.0, 0.0, 0.0), Quaternion::identity(), Vector3::one());
+
+        let combined = parent.combine(&child);
+
+        // (1, 0, 0) scaled to (2, 0, 0), then a quarter turn around y takes it to (0, 0, -2).
+        assert_close(combined.pos, Vector3::new(1.0, 2.0, 1.0));
+    }
+
+    #[test]
+    fn rotations_compose_and_scale_multiplies()
+    {
+        let parent = transform(Vector3::zero(), quarter_turn_y(), Vector3::new(2.0, 3.0, 4.0));
+        let child = transform(Vector3::zero(), quarter_turn_y(), Vector3::new(0.5, 2.0, 1.0));
+
+        let combined = parent.combine(&child);
+
+        // Two quarter turns make a half turn, which sends x to -x.
+        assert_close(Vector3::new(1.0, 0.0, 0.0).rotate_by(combined.rot), Vector3::new(-1.0, 0.0, 0.0));
+        assert_close(combined.scale, Vector3::new(1.0, 6.0, 4.0));
+    }
+
+    #[test]
+    fn multiply_applies_the_right_rotation_first()
+    {
+        let around_x = Quaternion::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), std::f32::consts::FRAC_PI_2);
+
+        // y turns to z around x, then z turns to x around y.
+        let rotated = Vector3::new(0.0, 1.0, 0.0).rotate_by(multiply(quarter_turn_y(), around_x));
+
+        assert_close(rotated, Vector3::new(1.0, 0.0, 0.0));
+    }
+}